@@ -0,0 +1,97 @@
+//! Reports how many allocations formatting an event takes.
+//!
+//! This is separate from the `format_event` benchmarks, so that counting allocations doesn't
+//! slow down the timings.
+
+use std::alloc::GlobalAlloc;
+use std::alloc::Layout;
+use std::alloc::System;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use tracing::span;
+use tracing::Dispatch;
+use tracing::Level;
+use tracing_human_layer::HumanLayer;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// Counts allocations, so we can report how many allocations formatting an event takes.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn emit_span_and_event() {
+    let span = span!(
+        Level::INFO,
+        "puppy doggy",
+        span_field = "span_field_value",
+        id = 12843205,
+        cute = "yes",
+    );
+    let _guard = span.enter();
+    tracing::info!(
+        event_field = "event_field_value",
+        kind = "silly",
+        puppy = "doggy",
+        "inspecting pawbs and other stuff and other stuff and other stuff and this and that and the other thing"
+    );
+}
+
+fn layer() -> HumanLayer<Vec<u8>> {
+    HumanLayer::new()
+        // 1MB probably enough?
+        .with_output_writer(Vec::<u8>::with_capacity(1_000_000))
+}
+
+/// Print the average number of allocations needed to emit a span and event.
+fn report_allocations(name: &str, dispatch: &Dispatch) {
+    const ITERATIONS: usize = 1_000;
+    tracing::dispatcher::with_default(dispatch, || {
+        // Warm up any per-thread buffers.
+        emit_span_and_event();
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        for _ in 0..ITERATIONS {
+            emit_span_and_event();
+        }
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+        println!(
+            "{name}: {:.1} allocations per event",
+            allocations as f64 / ITERATIONS as f64
+        );
+    });
+}
+
+fn main() {
+    owo_colors::set_override(true);
+
+    report_allocations(
+        "colors, wrapping",
+        &Dispatch::new(tracing_subscriber::registry().with(layer())),
+    );
+    report_allocations(
+        "no colors, no wrapping",
+        &Dispatch::new(
+            tracing_subscriber::registry()
+                .with(layer().with_color_output(false).with_textwrap_options(None)),
+        ),
+    );
+}
@@ -1,11 +1,19 @@
+use std::time::Duration;
+use std::time::Instant;
+
 use criterion::criterion_group;
 use criterion::criterion_main;
 use criterion::Criterion;
 use tracing::span;
+use tracing::span::EnteredSpan;
+use tracing::Dispatch;
 use tracing::Level;
 use tracing_human_layer::HumanLayer;
 use tracing_subscriber::layer::SubscriberExt;
 
+/// The number of threads to log from concurrently in the multi-threaded benchmark.
+const THREADS: usize = 4;
+
 fn emit_span_and_event() {
     let span = span!(
         Level::INFO,
@@ -23,6 +31,32 @@ fn emit_span_and_event() {
     );
 }
 
+/// How many spans deep to nest in the deep-nesting benchmark.
+const DEPTH: usize = 16;
+
+/// Enter [`DEPTH`] nested spans.
+///
+/// The spans are entered once, outside of the benchmark loop, like long-lived `#[instrument]`
+/// spans in a deep call stack.
+fn enter_nested_spans() -> Vec<EnteredSpan> {
+    (0..DEPTH)
+        .map(|depth| {
+            span!(
+                Level::INFO,
+                "nested",
+                depth,
+                path = "/Users/wiggles/.config/nix/nix.conf",
+                kind = "silly",
+            )
+            .entered()
+        })
+        .collect()
+}
+
+fn emit_nested_event() {
+    tracing::info!(puppy = "doggy", "inspecting pawbs");
+}
+
 fn layer() -> HumanLayer<Vec<u8>> {
     HumanLayer::new()
         // 1MB probably enough?
@@ -33,8 +67,27 @@ fn layer_stderr() -> HumanLayer {
     HumanLayer::new()
 }
 
+/// Emit `iterations` spans and events, split across [`THREADS`] threads logging concurrently.
+fn emit_concurrently(dispatch: &Dispatch, iterations: u64) -> Duration {
+    let per_thread = iterations.div_ceil(THREADS as u64);
+    let start = Instant::now();
+    std::thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                tracing::dispatcher::with_default(dispatch, || {
+                    for _ in 0..per_thread {
+                        emit_span_and_event();
+                    }
+                })
+            });
+        }
+    });
+    start.elapsed()
+}
+
 pub fn criterion_benchmark(criterion: &mut Criterion) {
     owo_colors::set_override(true);
+
     let mut group = criterion.benchmark_group("format event");
 
     tracing::subscriber::with_default(tracing_subscriber::registry().with(layer_stderr()), || {
@@ -76,6 +129,28 @@ pub fn criterion_benchmark(criterion: &mut Criterion) {
             });
         },
     );
+
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(layer()), || {
+        let spans = enter_nested_spans();
+        group.bench_function(
+            format!("colors, wrapping, {DEPTH} nested spans"),
+            |bencher| bencher.iter(emit_nested_event),
+        );
+        // Exit the spans from the inside out.
+        spans.into_iter().rev().for_each(drop);
+    });
+
+    // Every thread shares one writer, so this measures contention on the writer's lock.
+    let dispatch = Dispatch::new(tracing_subscriber::registry().with(layer()));
+    group.bench_function(format!("colors, wrapping, {THREADS} threads"), |bencher| {
+        bencher.iter_custom(|iterations| emit_concurrently(&dispatch, iterations))
+    });
+
+    let dispatch = Dispatch::new(tracing_subscriber::registry().with(layer_stderr()));
+    group.bench_function(
+        format!("colors, wrapping, stderr, {THREADS} threads"),
+        |bencher| bencher.iter_custom(|iterations| emit_concurrently(&dispatch, iterations)),
+    );
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -1,5 +1,8 @@
+use std::error::Error;
+use std::fmt::Display;
+
 use pico_args::Arguments;
-use supports_color::Stream;
+use tracing_human_layer::ShouldColor;
 use tracing_human_layer::TextWrapOptionsOwned;
 use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::layer::SubscriberExt;
@@ -60,16 +63,11 @@ fn main() {
 
     let layer = HumanLayer::default()
         .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
-        .with_color_output(
-            args.color
-                || supports_color::on(if args.stdout {
-                    Stream::Stdout
-                } else {
-                    Stream::Stderr
-                })
-                .map(|level| level.has_basic)
-                .unwrap_or_default(),
-        )
+        .with_color_output(if args.color {
+            ShouldColor::Always
+        } else {
+            ShouldColor::Auto
+        })
         .with_textwrap_options(if args.wrap {
             let mut opts = TextWrapOptionsOwned::new();
             if let Some(width) = args.width {
@@ -82,7 +80,7 @@ fn main() {
 
     if args.stdout {
         registry
-            .with(layer.with_output_writer(std::io::stdout()))
+            .with(layer.with_output_writer_detect(std::io::stdout()))
             .init();
     } else {
         registry.with(layer).init();
@@ -107,6 +105,12 @@ fn emit_events() {
         "Info event."
     );
 
+    let error = ConfigError(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "No such file or directory",
+    ));
+    tracing::error!(error = &error as &dyn Error, "Failed to start.");
+
     let span = tracing::info_span!("my-span", path = "my/cool/path.txt");
     let _guard = span.enter();
 
@@ -134,3 +138,18 @@ fn emit_events() {
         "Info event."
     );
 }
+
+#[derive(Debug)]
+struct ConfigError(std::io::Error);
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to read `config.toml`")
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.0)
+    }
+}
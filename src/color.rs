@@ -1,11 +1,280 @@
+use std::borrow::Cow;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Cursor;
+use std::io::LineWriter;
+use std::io::Sink;
+use std::io::Stderr;
+use std::io::StderrLock;
+use std::io::Stdout;
+use std::io::StdoutLock;
+use std::io::Write;
+
+#[cfg(doc)]
+use crate::Destination;
+#[cfg(doc)]
+use crate::HumanLayer;
+#[cfg(doc)]
+use crate::NonBlocking;
+
 /// Should we color a piece of output?
 ///
-/// This is nicer than a [`bool`], but I'm not sure how it should interact with
-/// `supports_color`.
-#[derive(Debug, Clone, Copy)]
-pub(crate) enum ShouldColor {
+/// This is nicer than a [`bool`], and a `bool` can be converted into a [`ShouldColor`] with
+/// [`From`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShouldColor {
     /// Always color the output.
     Always,
     /// Never color the output.
     Never,
+    /// Color the output if the writer is a terminal and the environment doesn't say otherwise.
+    ///
+    /// This is decided when the [`HumanLayer`]'s writer is set, so output redirected to a file or
+    /// pipe is written without ANSI escapes. In order of precedence:
+    ///
+    /// 1. If [`NO_COLOR`](https://no-color.org/) is set and non-empty, don't color the output.
+    /// 2. If [`CLICOLOR_FORCE`](https://bixense.com/clicolors/) or `FORCE_COLOR` is set to a
+    ///    value other than `0`, color the output.
+    /// 3. If `CLICOLOR` is `0` or `TERM` is `dumb`, don't color the output.
+    /// 4. Otherwise, color the output if the writer is a terminal (see
+    ///    [`HumanLayer::with_output_writer_detect`] and
+    ///    [`HumanLayer::with_output_writer_terminal`]).
+    Auto,
+}
+
+impl ShouldColor {
+    /// Resolve [`ShouldColor::Auto`] into [`ShouldColor::Always`] or [`ShouldColor::Never`] for
+    /// a writer, using the process environment.
+    pub(crate) fn resolve(self, is_terminal: bool) -> Self {
+        self.resolve_with(is_terminal, |name| std::env::var_os(name))
+    }
+
+    fn resolve_with(self, is_terminal: bool, var: impl Fn(&str) -> Option<OsString>) -> Self {
+        match self {
+            ShouldColor::Always | ShouldColor::Never => self,
+            ShouldColor::Auto => {
+                // Unset and empty variables are treated the same.
+                let var = |name: &str| var(name).filter(|value| !value.is_empty());
+                let forced =
+                    |name: &str| var(name).is_some_and(|value| value != "0" && value != "false");
+
+                if var("NO_COLOR").is_some() {
+                    ShouldColor::Never
+                } else if forced("CLICOLOR_FORCE") || forced("FORCE_COLOR") {
+                    ShouldColor::Always
+                } else if var("CLICOLOR").is_some_and(|value| value == "0")
+                    || var("TERM").is_some_and(|value| value == "dumb")
+                {
+                    ShouldColor::Never
+                } else if is_terminal {
+                    ShouldColor::Always
+                } else {
+                    ShouldColor::Never
+                }
+            }
+        }
+    }
+}
+
+impl From<bool> for ShouldColor {
+    fn from(color: bool) -> Self {
+        if color {
+            ShouldColor::Always
+        } else {
+            ShouldColor::Never
+        }
+    }
+}
+
+/// A writer which may be connected to a terminal.
+///
+/// This is used to resolve [`ShouldColor::Auto`] for a [`HumanLayer`]'s or [`Destination`]'s
+/// writer, and to pass through whether a [`NonBlocking`] writer's output is a terminal. The default implementation of
+/// [`MaybeTerminal::is_terminal`] returns `false`, so a custom writer can opt in with an empty
+/// `impl`.
+pub trait MaybeTerminal {
+    /// Is this writer connected to a terminal?
+    fn is_terminal(&self) -> bool {
+        false
+    }
+}
+
+impl MaybeTerminal for Stderr {
+    fn is_terminal(&self) -> bool {
+        std::io::IsTerminal::is_terminal(self)
+    }
+}
+
+impl MaybeTerminal for StderrLock<'_> {
+    fn is_terminal(&self) -> bool {
+        std::io::IsTerminal::is_terminal(self)
+    }
+}
+
+impl MaybeTerminal for Stdout {
+    fn is_terminal(&self) -> bool {
+        std::io::IsTerminal::is_terminal(self)
+    }
+}
+
+impl MaybeTerminal for StdoutLock<'_> {
+    fn is_terminal(&self) -> bool {
+        std::io::IsTerminal::is_terminal(self)
+    }
+}
+
+impl MaybeTerminal for File {
+    fn is_terminal(&self) -> bool {
+        std::io::IsTerminal::is_terminal(self)
+    }
+}
+
+impl<W> MaybeTerminal for LineWriter<W>
+where
+    W: MaybeTerminal + Write,
+{
+    fn is_terminal(&self) -> bool {
+        self.get_ref().is_terminal()
+    }
+}
+
+impl<W> MaybeTerminal for BufWriter<W>
+where
+    W: MaybeTerminal + Write,
+{
+    fn is_terminal(&self) -> bool {
+        self.get_ref().is_terminal()
+    }
+}
+
+impl<W> MaybeTerminal for &mut W
+where
+    W: MaybeTerminal + ?Sized,
+{
+    fn is_terminal(&self) -> bool {
+        (**self).is_terminal()
+    }
+}
+
+impl<W> MaybeTerminal for Box<W>
+where
+    W: MaybeTerminal + ?Sized,
+{
+    fn is_terminal(&self) -> bool {
+        (**self).is_terminal()
+    }
+}
+
+impl MaybeTerminal for Vec<u8> {}
+
+impl<T> MaybeTerminal for Cursor<T> {}
+
+impl MaybeTerminal for Sink {}
+
+/// Remove ANSI escape sequences (like colors) from text which was formatted for a colored
+/// writer.
+pub(crate) fn strip_ansi(text: &str) -> Cow<'_, str> {
+    if !text.contains('\x1b') {
+        return Cow::Borrowed(text);
+    }
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+        } else if chars.clone().next() == Some('[') {
+            // A control sequence: `ESC [`, parameters, and a final byte in `@` through `~`.
+            chars.next();
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        } else {
+            // Other escapes are two characters long.
+            chars.next();
+        }
+    }
+    Cow::Owned(stripped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(is_terminal: bool, env: &[(&str, &str)]) -> ShouldColor {
+        ShouldColor::Auto.resolve_with(is_terminal, |name| {
+            env.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.into())
+        })
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("copy"), "copy");
+        assert_eq!(
+            strip_ansi("copy{\x1b[1mpath\x1b[0m\x1b[2m=\x1b[0ma.txt}"),
+            "copy{path=a.txt}"
+        );
+        assert_eq!(strip_ansi("\x1b[38;5;208m日本語\x1b[0m"), "日本語");
+    }
+
+    #[test]
+    fn test_auto_terminal() {
+        assert_eq!(resolve(true, &[]), ShouldColor::Always);
+        assert_eq!(resolve(false, &[]), ShouldColor::Never);
+    }
+
+    #[test]
+    fn test_auto_no_color() {
+        assert_eq!(resolve(true, &[("NO_COLOR", "1")]), ShouldColor::Never);
+        assert_eq!(resolve(true, &[("NO_COLOR", "")]), ShouldColor::Always);
+        assert_eq!(
+            resolve(true, &[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")]),
+            ShouldColor::Never
+        );
+    }
+
+    #[test]
+    fn test_auto_force() {
+        assert_eq!(
+            resolve(false, &[("CLICOLOR_FORCE", "1")]),
+            ShouldColor::Always
+        );
+        assert_eq!(
+            resolve(false, &[("CLICOLOR_FORCE", "0")]),
+            ShouldColor::Never
+        );
+        assert_eq!(resolve(false, &[("FORCE_COLOR", "3")]), ShouldColor::Always);
+        assert_eq!(
+            resolve(false, &[("FORCE_COLOR", "false")]),
+            ShouldColor::Never
+        );
+        assert_eq!(
+            resolve(false, &[("FORCE_COLOR", "1"), ("TERM", "dumb")]),
+            ShouldColor::Always
+        );
+    }
+
+    #[test]
+    fn test_auto_disabled() {
+        assert_eq!(resolve(true, &[("CLICOLOR", "0")]), ShouldColor::Never);
+        assert_eq!(resolve(true, &[("CLICOLOR", "1")]), ShouldColor::Always);
+        assert_eq!(resolve(true, &[("TERM", "dumb")]), ShouldColor::Never);
+        assert_eq!(resolve(true, &[("TERM", "xterm")]), ShouldColor::Always);
+    }
+
+    #[test]
+    fn test_explicit() {
+        assert_eq!(
+            ShouldColor::Always.resolve_with(false, |_| Some("1".into())),
+            ShouldColor::Always
+        );
+        assert_eq!(
+            ShouldColor::Never.resolve_with(true, |_| None),
+            ShouldColor::Never
+        );
+    }
 }
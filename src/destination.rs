@@ -0,0 +1,182 @@
+//! Writers which some of a [`HumanLayer`]'s events are routed to.
+
+use std::fmt::Debug;
+use std::io;
+use std::io::Write;
+use std::time::Duration;
+use std::time::Instant;
+
+use parking_lot::Mutex;
+
+use crate::LastSpans;
+use crate::MaybeTerminal;
+use crate::OutputWriter;
+use crate::ProgressBars;
+use crate::ShouldColor;
+use crate::SpacingState;
+use crate::StatusFooter;
+use crate::TextWrapOptionsOwned;
+
+#[cfg(doc)]
+use crate::HumanLayer;
+#[cfg(doc)]
+use crate::TimestampFormat;
+
+/// A writer which some of a [`HumanLayer`]'s events are written to instead of its output
+/// writer, with its own color and text wrapping options. See [`HumanLayer::with_route`].
+///
+/// Blank lines between events are decided separately for each destination.
+pub struct Destination {
+    /// Whether to color the output, as configured.
+    color_output: ShouldColor,
+    /// Whether the writer is connected to a terminal.
+    is_terminal: bool,
+    /// Whether to color the output, with [`ShouldColor::Auto`] resolved for the writer.
+    pub(crate) color: ShouldColor,
+    /// Options for wrapping text, if any.
+    pub(crate) textwrap_options: Option<TextWrapOptionsOwned>,
+    pub(crate) output: Mutex<Output<Box<dyn Write + Send>>>,
+}
+
+impl Destination {
+    /// Construct a new [`Destination`] that writes to the given writer.
+    ///
+    /// Like [`HumanLayer::new`], output is colored and wrapped to the width of the terminal by
+    /// default. [`MaybeTerminal`] is used to check whether the writer is connected to a
+    /// terminal; for writers which don't implement it, use [`Destination::new_terminal`].
+    pub fn new<W>(writer: W) -> Self
+    where
+        W: Write + MaybeTerminal + Send + 'static,
+    {
+        let is_terminal = writer.is_terminal();
+        Self::new_terminal(writer, is_terminal)
+    }
+
+    /// Construct a new [`Destination`] that writes to the given writer, and whether it's
+    /// connected to a terminal.
+    ///
+    /// This works for any writer, including ones which don't implement [`MaybeTerminal`]:
+    ///
+    /// ```no_run
+    /// # use std::net::TcpStream;
+    /// # use tracing_human_layer::Destination;
+    /// # use tracing_human_layer::HumanLayer;
+    /// let stream = TcpStream::connect("127.0.0.1:9000")?;
+    /// let layer = HumanLayer::new().with_tee(
+    ///     Destination::new_terminal(stream, false).with_textwrap_options(None),
+    /// );
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn new_terminal<W>(writer: W, is_terminal: bool) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        Self {
+            color_output: ShouldColor::Always,
+            is_terminal,
+            color: ShouldColor::Always,
+            textwrap_options: Some(TextWrapOptionsOwned::new()),
+            output: Mutex::new(Output::new(Box::new(writer))),
+        }
+    }
+
+    /// Set the output coloring for this destination.
+    ///
+    /// [`ShouldColor::Auto`] is resolved for this destination's writer.
+    pub fn with_color_output(mut self, color_output: impl Into<ShouldColor>) -> Self {
+        self.color_output = color_output.into();
+        self.color = self.color_output.resolve(self.is_terminal);
+        self
+    }
+
+    /// Set the [`textwrap::Options`] for this destination.
+    ///
+    /// If `None`, no wrapping is performed.
+    pub fn with_textwrap_options(mut self, textwrap_options: Option<TextWrapOptionsOwned>) -> Self {
+        self.textwrap_options = textwrap_options;
+        self
+    }
+}
+
+impl Debug for Destination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Destination")
+            .field("color_output", &self.color_output)
+            .field("color", &self.color)
+            .field("textwrap_options", &self.textwrap_options)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A writer, along with the state which must be updated atomically with writes to it.
+#[derive(Debug)]
+pub(crate) struct Output<W> {
+    pub(crate) writer: W,
+    /// Tracks the previous event, to decide on blank lines between events.
+    ///
+    /// This is kept behind the same lock as the `writer`, so that concurrent events can't both
+    /// decide to print (or skip) a blank line.
+    pub(crate) spacing: SpacingState,
+    /// The spans of the previous event, to decide whether to write the next event's spans.
+    pub(crate) last_spans: LastSpans,
+    /// Progress bars for open spans, drawn below the other output on a terminal.
+    pub(crate) progress: ProgressBars,
+    /// The spans which are currently entered, drawn below the progress bars on a terminal.
+    pub(crate) status: StatusFooter,
+    /// When the previous event was written, for [`TimestampFormat::Delta`] timestamps.
+    pub(crate) last_event: Option<Instant>,
+    /// How many lines of progress bars and status footer are currently drawn.
+    live_lines: usize,
+}
+
+impl<W> Output<W> {
+    pub(crate) fn new(writer: W) -> Self {
+        Self {
+            writer,
+            spacing: SpacingState::default(),
+            last_spans: LastSpans::default(),
+            progress: ProgressBars::default(),
+            status: StatusFooter::default(),
+            last_event: None,
+            live_lines: 0,
+        }
+    }
+}
+
+impl<W> Output<W>
+where
+    W: OutputWriter,
+{
+    /// Erase the progress bars and status footer, leaving the cursor where they started.
+    pub(crate) fn clear_live(&mut self) -> io::Result<()> {
+        if self.live_lines > 0 {
+            // Move the cursor up to the first line, then erase everything below it.
+            let clear = format!("\r\x1b[{}A\x1b[J", self.live_lines);
+            self.writer.write_output(None, clear.as_bytes())?;
+            self.live_lines = 0;
+        }
+        Ok(())
+    }
+
+    /// Draw the progress bars and then the status footer, each line fitting in `width` columns.
+    pub(crate) fn draw_live(
+        &mut self,
+        width: usize,
+        color: ShouldColor,
+        status_threshold: Duration,
+    ) -> io::Result<()> {
+        let lines = self.progress.lines(width, color).chain(self.status.lines(
+            width,
+            color,
+            status_threshold,
+        ));
+        let mut drawn = String::new();
+        for line in lines {
+            drawn.push_str(&line);
+            drawn.push('\n');
+            self.live_lines += 1;
+        }
+        self.writer.write_output(None, drawn.as_bytes())?;
+        self.writer.flush_output()
+    }
+}
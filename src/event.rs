@@ -1,16 +1,21 @@
 use std::borrow::Cow;
+use std::cell::Cell;
+use std::error::Error;
 use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Display;
-use std::sync::atomic::AtomicBool;
-use std::sync::atomic::Ordering;
 
+use textwrap::core::display_width;
 use tracing::field::Field;
 use tracing::field::Visit;
 
 use crate::style::IntoConditionalColor;
 use crate::textwrap::TextWrapOptionsExt;
+use crate::FieldLayout;
+use crate::FieldValue;
 use crate::ShouldColor;
+use crate::SpanDisplay;
+use crate::SpanFieldLayout;
 use crate::SpanInfo;
 
 use super::HumanFields;
@@ -18,37 +23,164 @@ use super::Style;
 
 #[derive(Debug)]
 pub(crate) struct HumanEvent<'a> {
+    /// Set in [`Display::fmt`] if the message takes more than one line, so that the
+    /// [`crate::HumanLayer`] can decide whether to print blank lines around the event.
+    ///
     /// We need to modify this in [`Display::fmt`], so it must be mutable through a `&self`
-    /// reference. We can either have a separate [`AtomicBool`] like this (no contention) or borrow the
-    /// [`AtomicBool`] from the [`crate::HumanLayer`].
-    pub(crate) last_event_was_long: AtomicBool,
+    /// reference.
+    pub(crate) is_long: Cell<bool>,
     pub(crate) style: Cow<'a, Style>,
     pub(crate) textwrap_options: Option<textwrap::Options<'a>>,
     pub(crate) color: ShouldColor,
     /// Spans, in root-to-current (outside-in) order.
     pub(crate) spans: Vec<SpanInfo>,
     pub(crate) fields: HumanFields,
+    /// How to lay out the `fields`.
+    pub(crate) field_layout: FieldLayout,
+    /// How to lay out span fields which don't fit on one line.
+    pub(crate) span_field_layout: SpanFieldLayout,
+    /// If spans are shown as a tree, how many spans deep the event is. Each span is shown as
+    /// a guide before every line of the event, instead of listing the spans after it.
+    pub(crate) tree_depth: Option<usize>,
+    /// A formatted timestamp to print before the event, if any.
+    pub(crate) timestamp: Option<String>,
 }
 
 impl<'a> Visit for HumanEvent<'a> {
     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
-        self.fields.record_field(field.name(), format!("{value:?}"))
+        self.fields.record_debug(field, value)
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.fields.record_str(field, value)
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.fields.record_i64(field, value)
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields.record_u64(field, value)
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.fields.record_f64(field, value)
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.fields.record_bool(field, value)
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn Error + 'static)) {
+        self.fields.record_error(field, value)
     }
 }
 
 impl<'a> Display for HumanEvent<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let indent_colored = self
-            .style
-            .initial_indent_text
-            .colored(self.color, self.style.initial_indent)
-            .to_string();
+        self.fmt_body(f)?;
+        self.fmt_spans(f)
+    }
+}
+
+/// An event's message and fields, without its spans.
+pub(crate) struct EventBody<'e, 'a>(&'e HumanEvent<'a>);
+
+impl Display for EventBody<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_body(f)
+    }
+}
+
+/// An event's spans, written after its [`EventBody`].
+pub(crate) struct EventSpans<'e, 'a>(&'e HumanEvent<'a>);
+
+impl Display for EventSpans<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_spans(f)
+    }
+}
+
+impl<'a> HumanEvent<'a> {
+    /// The event's message and fields, without its spans.
+    pub(crate) fn body(&self) -> EventBody<'_, 'a> {
+        EventBody(self)
+    }
+
+    /// The event's spans.
+    pub(crate) fn span_context(&self) -> EventSpans<'_, 'a> {
+        EventSpans(self)
+    }
+
+    /// A marker written instead of the event's spans when they're the same as the previous
+    /// event's spans.
+    pub(crate) fn unchanged_spans_marker(&self) -> String {
+        format!(
+            "{}{}\n",
+            self.subsequent_indent(),
+            "〃".colored(self.color, self.style.span_in)
+        )
+    }
+
+    /// The guides for each span the event is in, if spans are shown as a tree.
+    fn tree_guides(&self) -> String {
+        match self.tree_depth {
+            Some(depth) if depth > 0 => self
+                .style
+                .tree_guide_text
+                .repeat(depth)
+                .colored(self.color, self.style.tree_guide)
+                .to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// The indent for every line after the first.
+    ///
+    /// If there's a timestamp, later lines are indented to line up with the text after it.
+    fn subsequent_indent(&self) -> Cow<'_, str> {
+        let tree_guides = self.tree_guides();
+        match &self.timestamp {
+            Some(timestamp) => Cow::Owned(format!(
+                "{:width$}{tree_guides}{}",
+                "",
+                self.style.subsequent_indent_text,
+                width = display_width(timestamp) + 1,
+            )),
+            None if !tree_guides.is_empty() => Cow::Owned(format!(
+                "{tree_guides}{}",
+                self.style.subsequent_indent_text
+            )),
+            None => Cow::Borrowed(&*self.style.subsequent_indent_text),
+        }
+    }
+
+    fn fmt_body(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The timestamp and tree guides go before the first line.
+        let mut prefix = match &self.timestamp {
+            Some(timestamp) => format!("{} ", timestamp.colored(self.color, self.style.timestamp)),
+            None => String::new(),
+        };
+        prefix.push_str(&self.tree_guides());
+        let subsequent_indent = self.subsequent_indent();
+
+        // Don't write escapes around an empty indent, like the one for tree headers.
+        let indent_colored = if self.style.initial_indent_text.is_empty() {
+            prefix.clone()
+        } else {
+            format!(
+                "{prefix}{}",
+                self.style
+                    .initial_indent_text
+                    .colored(self.color, self.style.initial_indent)
+            )
+        };
 
         let options = self.textwrap_options.as_ref().map(|options| {
             options
                 .clone()
                 .initial_indent(&indent_colored)
-                .subsequent_indent(&self.style.subsequent_indent_text)
+                .subsequent_indent(&subsequent_indent)
         });
 
         let mut message = match &options {
@@ -64,20 +196,22 @@ impl<'a> Display for HumanEvent<'a> {
             }
         };
 
-        // If there's only one field, and it fits on the same line as the message, put it on the
-        // same line. Otherwise, we use the 'long format' with each field on a separate line.
-        let short_format = options
-            .as_ref()
-            .map(|options| self.fields.use_short_format(options.width))
-            .unwrap_or(true);
-
-        if short_format {
-            for (name, value) in &self.fields.fields {
-                message.push_str(&format!(
-                    " {}",
-                    self.style.style_field(self.color, name, value)
-                ));
-            }
+        // Put the fields which fit on the same line as the message. The rest use the 'long
+        // format', with each field on a separate line.
+        let inline_fields = self.fields.inline_fields(
+            self.field_layout,
+            options
+                .as_ref()
+                .map(|options| options.width.saturating_sub(display_width(&indent_colored))),
+        );
+        let (inline_fields, long_fields) = self.fields.fields.split_at(inline_fields);
+
+        for (name, value) in inline_fields {
+            message.push_str(&format!(
+                " {}",
+                self.style
+                    .style_field(self.color, name, value.display(self.fields.quote_strings))
+            ));
         }
 
         // Next, color the message _before_ wrapping it. If you wrap before coloring,
@@ -90,52 +224,283 @@ impl<'a> Display for HumanEvent<'a> {
 
         let lines = options.wrap(&message_colored);
 
-        // If there's more than one line of message, add a blank line before and after the message.
-        // This doesn't account for fields, but I think that's fine?
-        let add_blank_lines = lines.len() > 1;
-        // Store `add_blank_lines` and fetch the previous value:
-        let last_event_was_long = self
-            .last_event_was_long
-            .swap(add_blank_lines, Ordering::SeqCst);
-        if add_blank_lines && !last_event_was_long {
-            writeln!(f)?;
-        };
+        // If there's more than one line of message, the event is long, and we may add blank lines
+        // around it. This doesn't account for fields, but I think that's fine?
+        self.is_long.set(lines.len() > 1);
 
         // Write the actual message, line by line.
+        if options.is_none() {
+            // If we're not wrapping, the prefix isn't part of the `initial_indent`.
+            write!(f, "{prefix}")?;
+        }
         for line in &lines {
             writeln!(f, "{line}")?;
         }
 
-        // Add fields, one per line, at the end.
-        if !short_format {
-            for (name, value) in &self.fields.fields {
-                writeln!(
-                    f,
-                    "{}{}",
-                    self.style.subsequent_indent_text,
-                    self.style.style_field(self.color, name, value)
-                )?;
+        // Add the rest of the fields, one per line, at the end.
+        let name_width = match self.field_layout {
+            FieldLayout::Aligned => long_fields
+                .iter()
+                .map(|(name, _)| display_width(name))
+                .max()
+                .unwrap_or_default(),
+            _ => 0,
+        };
+        for (name, value) in long_fields {
+            match value {
+                FieldValue::Error { message, sources } => {
+                    // Continuation lines of the message line up with its sources.
+                    let sources_indent = format!("{subsequent_indent}  ");
+                    self.fmt_field_with_indent(
+                        f,
+                        &subsequent_indent,
+                        &sources_indent,
+                        name,
+                        name_width,
+                        message,
+                    )?;
+                    self.fmt_error_sources(f, &subsequent_indent, sources)?;
+                }
+                _ => {
+                    self.fmt_field(
+                        f,
+                        &subsequent_indent,
+                        name,
+                        name_width,
+                        value.display(self.fields.quote_strings),
+                    )?;
+                }
             }
         }
 
-        // Add spans, one per line, at the end.
-        // TODO: Short format for spans?
-        for span in self.spans.iter().rev() {
-            writeln!(
-                f,
-                "{indent}{in_}{name}{fields}",
-                indent = self.style.subsequent_indent_text,
-                in_ = "in ".colored(self.color, self.style.span_in),
-                name = span.name.colored(self.color, self.style.span_name),
-                fields = span.fields,
-            )?;
+        Ok(())
+    }
+
+    fn fmt_spans(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.tree_depth.is_some() {
+            // The spans are shown as a tree instead.
+            return Ok(());
+        }
+        let subsequent_indent = self.subsequent_indent();
+        match self.style.span_display {
+            SpanDisplay::Full => {
+                for span in self.spans.iter().rev() {
+                    self.fmt_span(f, &subsequent_indent, &[], span)?;
+                }
+            }
+            SpanDisplay::RootFirst => {
+                for span in &self.spans {
+                    self.fmt_span(f, &subsequent_indent, &[], span)?;
+                }
+            }
+            SpanDisplay::Leaf => {
+                if let Some(span) = self.spans.last() {
+                    self.fmt_span(f, &subsequent_indent, &[], span)?;
+                }
+            }
+            SpanDisplay::Breadcrumb => {
+                if let Some((span, ancestors)) = self.spans.split_last() {
+                    self.fmt_span(f, &subsequent_indent, ancestors, span)?;
+                }
+            }
+            SpanDisplay::MaxDepth(max_depth) => {
+                for span in self.spans.iter().rev().take(max_depth) {
+                    self.fmt_span(f, &subsequent_indent, &[], span)?;
+                }
+            }
+            SpanDisplay::None => {}
         }
 
-        // If there's more than one line of output, add a blank line before and after the message.
-        if add_blank_lines {
-            writeln!(f)?;
+        Ok(())
+    }
+
+    /// Write a field on its own line, like `name=value`.
+    ///
+    /// If wrapping is enabled, the value is wrapped with continuation lines indented to line up
+    /// with the start of the value:
+    ///
+    /// ```text
+    /// command=cargo build --workspace --all-targets
+    ///         --release
+    /// ```
+    fn fmt_field(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        indent: &str,
+        name: &str,
+        name_width: usize,
+        value: impl Display,
+    ) -> fmt::Result {
+        let subsequent_indent = format!(
+            "{indent}{:width$}",
+            "",
+            // The name, padded to `name_width`, then the `=`.
+            width = name_width.max(display_width(name)) + 1,
+        );
+        self.fmt_field_with_indent(f, indent, &subsequent_indent, name, name_width, value)
+    }
+
+    /// Like [`Self::fmt_field`], but with the indent for continuation lines given explicitly.
+    fn fmt_field_with_indent(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        indent: &str,
+        subsequent_indent: &str,
+        name: &str,
+        name_width: usize,
+        value: impl Display,
+    ) -> fmt::Result {
+        let field = self
+            .style
+            .style_field(self.color, name, value)
+            .with_name_width(name_width);
+
+        match &self.textwrap_options {
+            Some(options) => {
+                let field = field.to_string();
+                let options = options
+                    .clone()
+                    .initial_indent(indent)
+                    .subsequent_indent(subsequent_indent);
+                for line in options.wrap(&field) {
+                    writeln!(f, "{line}")?;
+                }
+                Ok(())
+            }
+            None => writeln!(f, "{indent}{field}"),
+        }
+    }
+
+    /// Write a span's name and fields, like `in copy{path=a.txt}`, after the names of its
+    /// `ancestors`, if any, like `in outer › copy{path=a.txt}`.
+    ///
+    /// If the fields don't fit within the wrap width, lines are broken between fields, and
+    /// continuation lines are indented to line up with the span's name. A single field which is
+    /// wider than the wrap width isn't broken.
+    fn fmt_span(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        indent: &str,
+        ancestors: &[SpanInfo],
+        span: &SpanInfo,
+    ) -> fmt::Result {
+        let in_ = "in ";
+        let separator = " › ";
+        let mut line = format!("{indent}{}", in_.colored(self.color, self.style.span_in));
+        for ancestor in ancestors {
+            line.push_str(&format!(
+                "{}{}",
+                ancestor.name.colored(self.color, self.style.span_name),
+                separator.colored(self.color, self.style.span_in),
+            ));
+        }
+        line.push_str(&format!(
+            "{}",
+            span.name.colored(self.color, self.style.span_name)
+        ));
+        let fields = span.fields.get(self.color);
+        let Some((first, rest)) = fields.split_first() else {
+            return writeln!(f, "{line}");
         };
 
+        let open = "{".colored(self.color, self.style.span_name);
+        let close = "}".colored(self.color, self.style.span_name);
+        let width = self.textwrap_options.as_ref().map(|options| options.width);
+        // The name, then the fields separated by spaces, in braces.
+        let one_line_width = display_width(&line)
+            + fields
+                .iter()
+                .map(|field| display_width(field))
+                .sum::<usize>()
+            + (fields.len() - 1)
+            + 2;
+        let fits_on_one_line = width.is_none_or(|width| one_line_width <= width);
+        if fits_on_one_line {
+            return writeln!(f, "{line}{open}{}{close}", fields.join(" "));
+        }
+
+        // Continuation lines line up under the name of the span which owns the fields, after
+        // any ancestors listed before it.
+        let ancestors_width = ancestors
+            .iter()
+            .map(|ancestor| display_width(ancestor.name) + display_width(separator))
+            .sum::<usize>();
+        let continuation_indent =
+            format!("{indent}{:width$}", "", width = in_.len() + ancestors_width);
+        line.push_str(&format!("{open}{first}"));
+        for (i, field) in rest.iter().enumerate() {
+            // The last field is followed by the closing brace.
+            let closing_width = if i == rest.len() - 1 { 1 } else { 0 };
+            let fits = match self.span_field_layout {
+                SpanFieldLayout::Wrap => width.is_none_or(|width| {
+                    display_width(&line) + 1 + display_width(field) + closing_width <= width
+                }),
+                SpanFieldLayout::OnePerLine => false,
+            };
+            if fits {
+                line.push(' ');
+            } else {
+                writeln!(f, "{line}")?;
+                line.clone_from(&continuation_indent);
+            }
+            line.push_str(field);
+        }
+        writeln!(f, "{line}{close}")
+    }
+
+    /// Write a numbered list of an error's sources, like this:
+    ///
+    /// ```text
+    /// error=Failed to read configuration
+    ///   caused by:
+    ///   1. Failed to open `config.toml`
+    ///   2. No such file or directory (os error 2)
+    /// ```
+    fn fmt_error_sources(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        indent: &str,
+        sources: &[String],
+    ) -> fmt::Result {
+        if sources.is_empty() {
+            return Ok(());
+        }
+
+        let indent = format!("{indent}  ");
+        writeln!(
+            f,
+            "{indent}{}",
+            "caused by:".colored(self.color, self.style.error_caused_by)
+        )?;
+
+        let number_width = sources.len().to_string().len();
+        // Continuation lines are indented to line up with the text after the number.
+        let subsequent_indent = format!("{indent}{:width$}", "", width = number_width + 2);
+        for (i, source) in sources.iter().enumerate() {
+            let number = format!("{:>number_width$}. ", i + 1);
+            let initial_indent = format!(
+                "{indent}{}",
+                number.colored(self.color, self.style.error_caused_by)
+            );
+            let source_colored = source
+                .as_str()
+                .colored(self.color, self.style.error_source)
+                .to_string();
+            let options = self.textwrap_options.as_ref().map(|options| {
+                options
+                    .clone()
+                    .initial_indent(&initial_indent)
+                    .subsequent_indent(&subsequent_indent)
+            });
+            let lines = match &options {
+                Some(_) => options.wrap(&source_colored),
+                None => vec![format!("{initial_indent}{source_colored}").into()],
+            };
+            for line in &lines {
+                writeln!(f, "{line}")?;
+            }
+        }
+
         Ok(())
     }
 }
@@ -143,6 +508,7 @@ impl<'a> Display for HumanEvent<'a> {
 #[cfg(test)]
 mod tests {
     use crate::style::LayerStyles;
+    use crate::QuoteStrings;
     use crate::ShouldColor;
     use crate::TextWrapOptionsOwned;
 
@@ -151,6 +517,7 @@ mod tests {
     use expect_test::expect;
     use expect_test::Expect;
     use indoc::indoc;
+    use tracing::span::Id;
     use tracing::Level;
 
     // /!\   /!\   /!\   /!\   /!\   /!\   /!\   /!\
@@ -172,7 +539,7 @@ mod tests {
         let styles = LayerStyles::new();
         check(
             HumanEvent {
-                last_event_was_long: AtomicBool::new(false),
+                is_long: Cell::new(false),
                 style: styles.for_level(Level::INFO),
                 color: ShouldColor::Always,
                 fields: HumanFields {
@@ -181,9 +548,14 @@ mod tests {
                         "Checking access to Mercury repositories on GitHub over SSH".to_owned(),
                     ),
                     fields: Default::default(),
+                    quote_strings: QuoteStrings::WhenNeeded,
                 },
                 spans: vec![],
                 textwrap_options: Some((&TextWrapOptionsOwned::new()).into()),
+                field_layout: FieldLayout::Inline,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
             },
             expect![[r#"
                 [32m• [0mChecking access to Mercury repositories on GitHub over SSH
@@ -196,16 +568,21 @@ mod tests {
         let styles = LayerStyles::new();
         check(
             HumanEvent {
-                last_event_was_long: AtomicBool::new(false),
+                is_long: Cell::new(false),
                 style: styles.for_level(Level::INFO),
                 color: ShouldColor::Always,
                 fields: HumanFields {
                     extract_message: true,
                     message: Some("User `nix.conf` is already OK".to_owned()),
-                    fields: vec![("path", "/Users/wiggles/.config/nix/nix.conf".to_owned())],
+                    fields: vec![("path", "/Users/wiggles/.config/nix/nix.conf".into())],
+                    quote_strings: QuoteStrings::WhenNeeded,
                 },
                 spans: vec![],
                 textwrap_options: Some((&TextWrapOptionsOwned::new()).into()),
+                field_layout: FieldLayout::Inline,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
             },
             expect![[r#"
                 [32m• [0mUser `nix.conf` is already OK [1mpath[0m=/Users/wiggles/.config/nix/nix.conf
@@ -218,7 +595,7 @@ mod tests {
         let styles = LayerStyles::new();
         check(
             HumanEvent {
-                last_event_was_long: AtomicBool::new(false),
+                is_long: Cell::new(false),
                 style: styles.for_level(Level::INFO),
                 color: ShouldColor::Always,
                 fields: HumanFields {
@@ -228,11 +605,16 @@ mod tests {
                         "path",
                         // this field is too long to fit on one line, so we use the long format
                         "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
-                            .to_owned(),
+                            .into(),
                     )],
+                    quote_strings: QuoteStrings::WhenNeeded,
                 },
                 spans: vec![],
                 textwrap_options: Some((&TextWrapOptionsOwned::new()).into()),
+                field_layout: FieldLayout::Inline,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
             },
             expect![[r#"
                 [32m• [0mUser `nix.conf` is already OK
@@ -246,20 +628,24 @@ mod tests {
         let styles = LayerStyles::new();
         check(
             HumanEvent {
-                last_event_was_long: AtomicBool::new(false),
+                is_long: Cell::new(false),
                 style: styles.for_level(Level::INFO),
                 color: ShouldColor::Always,
                 fields: HumanFields {
                     extract_message: true,
                     message: Some("User `nix.conf` is already OK".to_owned()),
-                    // Multiple fields means we use the long format.
                     fields: vec![
-                        ("path", "~/.config/nix/nix.conf".to_owned()),
-                        ("user", "puppy".to_owned()),
+                        ("path", "~/.config/nix/nix.conf".into()),
+                        ("user", "puppy".into()),
                     ],
+                    quote_strings: QuoteStrings::WhenNeeded,
                 },
                 spans: vec![],
                 textwrap_options: Some((&TextWrapOptionsOwned::new()).into()),
+                field_layout: FieldLayout::OnePerLine,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
             },
             expect![[r#"
                 [32m• [0mUser `nix.conf` is already OK
@@ -270,57 +656,412 @@ mod tests {
     }
 
     #[test]
-    fn test_long_warning() {
+    fn test_inline_format() {
         let styles = LayerStyles::new();
         check(
             HumanEvent {
-                last_event_was_long: AtomicBool::new(false),
-                style: styles.for_level(Level::WARN),
+                is_long: Cell::new(false),
+                style: styles.for_level(Level::INFO),
                 color: ShouldColor::Always,
                 fields: HumanFields {
                     extract_message: true,
-                    message: Some(
-                        indoc!(
-                            "
-                            `nix doctor` found potential issues with your Nix installation:
-                            Running checks against store uri: daemon
-                            [FAIL] Multiple versions of nix found in PATH:
-                              /nix/store/lr32i0bdarx1iqsch4sy24jj1jkfw9vf-nix-2.11.0/bin
-                              /nix/store/s1j8d1x2jlfkb2ckncal8a700hid746p-nix-2.11.0/bin
+                    message: Some("User `nix.conf` is already OK".to_owned()),
+                    // Both fields fit on the same line as the message.
+                    fields: vec![
+                        ("path", "~/.config/nix/nix.conf".into()),
+                        ("user", "puppy".into()),
+                    ],
+                    quote_strings: QuoteStrings::WhenNeeded,
+                },
+                spans: vec![],
+                textwrap_options: Some((&TextWrapOptionsOwned::new()).into()),
+                field_layout: FieldLayout::Inline,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
+            },
+            expect![[r#"
+                [32m• [0mUser `nix.conf` is already OK [1mpath[0m=~/.config/nix/nix.conf [1muser[0m=puppy
+            "#]],
+        );
+    }
 
-                            [PASS] All profiles are gcroots.
-                            [PASS] Client protocol matches store protocol.
-                            "
-                        )
-                        .to_owned(),
-                    ),
-                    fields: vec![],
+    #[test]
+    fn test_aligned_format() {
+        let styles = LayerStyles::new();
+        check(
+            HumanEvent {
+                is_long: Cell::new(false),
+                style: styles.for_level(Level::INFO),
+                color: ShouldColor::Always,
+                fields: HumanFields {
+                    extract_message: true,
+                    message: Some("User `nix.conf` is already OK".to_owned()),
+                    fields: vec![
+                        ("path", "~/.config/nix/nix.conf".into()),
+                        ("user", "puppy".into()),
+                        ("permissions", "0644".into()),
+                    ],
+                    quote_strings: QuoteStrings::WhenNeeded,
                 },
                 spans: vec![],
                 textwrap_options: Some((&TextWrapOptionsOwned::new()).into()),
+                field_layout: FieldLayout::Aligned,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
+            },
+            expect![[r#"
+                [32m• [0mUser `nix.conf` is already OK
+                  [1mpath[0m       =~/.config/nix/nix.conf
+                  [1muser[0m       =puppy
+                  [1mpermissions[0m=0644
+            "#]],
+        );
+    }
+
+    #[test]
+    fn test_wide_indent_and_message() {
+        // `🚨 配置を確認 path=日本語` is 25 columns wide but 35 bytes long, so it fits in 30
+        // columns.
+        let styles = LayerStyles::new();
+        check(
+            HumanEvent {
+                is_long: Cell::new(false),
+                style: Cow::Owned(
+                    styles
+                        .for_level(Level::INFO)
+                        .into_owned()
+                        .with_initial_indent_text("🚨 ".into()),
+                ),
+                color: ShouldColor::Never,
+                fields: HumanFields {
+                    extract_message: true,
+                    message: Some("配置を確認".to_owned()),
+                    fields: vec![("path", "日本語".into())],
+                    quote_strings: QuoteStrings::WhenNeeded,
+                },
+                spans: vec![],
+                textwrap_options: Some((&TextWrapOptionsOwned::new().with_width(30)).into()),
+                field_layout: FieldLayout::Inline,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
             },
             expect![[r#"
+                🚨 配置を確認 path=日本語
+            "#]],
+        );
+    }
 
-                [33m⚠ [0m[33m`nix doctor` found potential issues with your Nix installation:
-                  Running checks against store uri: daemon
-                  [FAIL] Multiple versions of nix found in PATH:
-                    /nix/store/lr32i0bdarx1iqsch4sy24jj1jkfw9vf-nix-2.11.0/bin
-                    /nix/store/s1j8d1x2jlfkb2ckncal8a700hid746p-nix-2.11.0/bin
+    #[test]
+    fn test_wide_message_overflows() {
+        // `• 配置を確認しています path=日本語` is 21 characters but 34 columns wide, so it
+        // doesn't fit in 30 columns.
+        let styles = LayerStyles::new();
+        check(
+            HumanEvent {
+                is_long: Cell::new(false),
+                style: styles.for_level(Level::INFO),
+                color: ShouldColor::Never,
+                fields: HumanFields {
+                    extract_message: true,
+                    message: Some("配置を確認しています".to_owned()),
+                    fields: vec![("path", "日本語".into())],
+                    quote_strings: QuoteStrings::WhenNeeded,
+                },
+                spans: vec![],
+                textwrap_options: Some((&TextWrapOptionsOwned::new().with_width(30)).into()),
+                field_layout: FieldLayout::Inline,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
+            },
+            expect![[r#"
+                • 配置を確認しています
+                  path=日本語
+            "#]],
+        );
+    }
 
-                  [PASS] All profiles are gcroots.
-                  [PASS] Client protocol matches store protocol.
-                  [0m
+    #[test]
+    fn test_aligned_format_wide_names() {
+        // Combining marks take no columns, so `café` (with a combining accent) and `日本`
+        // both line up with `name`.
+        let styles = LayerStyles::new();
+        check(
+            HumanEvent {
+                is_long: Cell::new(false),
+                style: styles.for_level(Level::INFO),
+                color: ShouldColor::Never,
+                fields: HumanFields {
+                    extract_message: true,
+                    message: Some("Ordering 🦀".to_owned()),
+                    fields: vec![
+                        ("name", "puppy".into()),
+                        ("cafe\u{301}", "open".into()),
+                        ("日本", "🍣".into()),
+                    ],
+                    quote_strings: QuoteStrings::WhenNeeded,
+                },
+                spans: vec![],
+                textwrap_options: Some((&TextWrapOptionsOwned::new()).into()),
+                field_layout: FieldLayout::Aligned,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
+            },
+            expect![[r#"
+                • Ordering 🦀
+                  name=puppy
+                  café=open
+                  日本=🍣
+            "#]],
+        );
+    }
 
+    #[test]
+    fn test_wrap_field_value() {
+        let styles = LayerStyles::new();
+        check(
+            HumanEvent {
+                is_long: Cell::new(false),
+                style: styles.for_level(Level::INFO),
+                color: ShouldColor::Never,
+                fields: HumanFields {
+                    extract_message: true,
+                    message: Some("Running command".to_owned()),
+                    fields: vec![
+                        (
+                            "command",
+                            "cargo build --workspace --all-targets --release --features serde \
+                             --target-dir /tmp/target"
+                                .into(),
+                        ),
+                        ("cwd", "/home/puppy".into()),
+                    ],
+                    quote_strings: QuoteStrings::WhenNeeded,
+                },
+                spans: vec![],
+                textwrap_options: Some((&TextWrapOptionsOwned::new().with_width(40)).into()),
+                field_layout: FieldLayout::Aligned,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
+            },
+            expect![[r#"
+                • Running command
+                  command="cargo build --workspace
+                          --all-targets --release
+                          --features serde --target-dir
+                          /tmp/target"
+                  cwd    =/home/puppy
             "#]],
         );
     }
 
     #[test]
-    fn test_long_warning_last_was_long() {
+    fn test_wrap_multiline_field_value() {
+        // Each line of a multi-line value is indented under the start of the value.
         let styles = LayerStyles::new();
         check(
             HumanEvent {
-                last_event_was_long: AtomicBool::new(false),
+                is_long: Cell::new(false),
+                style: styles.for_level(Level::INFO),
+                color: ShouldColor::Never,
+                fields: HumanFields {
+                    extract_message: true,
+                    message: Some("Running command".to_owned()),
+                    fields: vec![(
+                        "stdout",
+                        "Compiling serde\nCompiling tracing\nFinished".into(),
+                    )],
+                    quote_strings: QuoteStrings::WhenNeeded,
+                },
+                spans: vec![],
+                textwrap_options: Some((&TextWrapOptionsOwned::new()).into()),
+                field_layout: FieldLayout::OnePerLine,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
+            },
+            expect![[r#"
+                • Running command
+                  stdout="Compiling serde
+                         Compiling tracing
+                         Finished"
+            "#]],
+        );
+    }
+
+    #[test]
+    fn test_wrap_field_value_break_words() {
+        // Long words in values are broken, depending on `break_words`.
+        let styles = LayerStyles::new();
+        check(
+            HumanEvent {
+                is_long: Cell::new(false),
+                style: styles.for_level(Level::INFO),
+                color: ShouldColor::Never,
+                fields: HumanFields {
+                    extract_message: true,
+                    message: Some("Running command".to_owned()),
+                    fields: vec![(
+                        "path",
+                        "/nix/store/lr32i0bdarx1iqsch4sy24jj1jkfw9vf-nix-2.11.0".into(),
+                    )],
+                    quote_strings: QuoteStrings::WhenNeeded,
+                },
+                spans: vec![],
+                textwrap_options: Some(
+                    (&TextWrapOptionsOwned::new()
+                        .with_width(30)
+                        .with_break_words(true))
+                        .into(),
+                ),
+                field_layout: FieldLayout::OnePerLine,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
+            },
+            expect![[r#"
+                • Running command
+                  path=/nix/store/lr32i0b
+                       darx1iqsch4sy24jj1jkfw9
+                       vf-nix-2.11.0
+            "#]],
+        );
+    }
+
+    #[test]
+    fn test_wrap_span_fields() {
+        // Lines are broken between span fields, under the span's name.
+        let styles = LayerStyles::new();
+        check(
+            HumanEvent {
+                is_long: Cell::new(false),
+                style: styles.for_level(Level::INFO),
+                color: ShouldColor::Never,
+                fields: HumanFields {
+                    extract_message: true,
+                    message: Some("Copying photos".to_owned()),
+                    fields: vec![],
+                    quote_strings: QuoteStrings::WhenNeeded,
+                },
+                spans: vec![SpanInfo {
+                    id: Id::from_u64(1),
+                    name: "copy",
+                    fields: vec![
+                        "from=/home/puppy/photos/2024".to_owned(),
+                        "to=/mnt/backup/photos/2024".to_owned(),
+                        "overwrite=false".to_owned(),
+                    ]
+                    .into(),
+                }],
+                textwrap_options: Some((&TextWrapOptionsOwned::new().with_width(60)).into()),
+                field_layout: FieldLayout::Inline,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
+            },
+            expect![[r#"
+                • Copying photos
+                  in copy{from=/home/puppy/photos/2024
+                     to=/mnt/backup/photos/2024 overwrite=false}
+            "#]],
+        );
+    }
+
+    #[test]
+    fn test_wrap_span_fields_breadcrumb() {
+        // Lines are broken under the name of the span which owns the fields, not its ancestors.
+        let styles = LayerStyles::new().with_span_display(SpanDisplay::Breadcrumb);
+        check(
+            HumanEvent {
+                is_long: Cell::new(false),
+                style: styles.for_level(Level::INFO),
+                color: ShouldColor::Never,
+                fields: HumanFields {
+                    extract_message: true,
+                    message: Some("Copying photos".to_owned()),
+                    fields: vec![],
+                    quote_strings: QuoteStrings::WhenNeeded,
+                },
+                spans: vec![
+                    SpanInfo {
+                        id: Id::from_u64(1),
+                        name: "backup",
+                        fields: vec!["drive=sdb".to_owned()].into(),
+                    },
+                    SpanInfo {
+                        id: Id::from_u64(2),
+                        name: "copy",
+                        fields: vec![
+                            "from=/home/puppy/photos/2024".to_owned(),
+                            "to=/mnt/backup/photos/2024".to_owned(),
+                        ]
+                        .into(),
+                    },
+                ],
+                textwrap_options: Some((&TextWrapOptionsOwned::new().with_width(60)).into()),
+                field_layout: FieldLayout::Inline,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
+            },
+            expect![[r#"
+                • Copying photos
+                  in backup › copy{from=/home/puppy/photos/2024
+                              to=/mnt/backup/photos/2024}
+            "#]],
+        );
+    }
+
+    #[test]
+    fn test_span_fields_one_per_line() {
+        let styles = LayerStyles::new();
+        check(
+            HumanEvent {
+                is_long: Cell::new(false),
+                style: styles.for_level(Level::INFO),
+                color: ShouldColor::Never,
+                fields: HumanFields {
+                    extract_message: true,
+                    message: Some("Copying photos".to_owned()),
+                    fields: vec![],
+                    quote_strings: QuoteStrings::WhenNeeded,
+                },
+                spans: vec![SpanInfo {
+                    id: Id::from_u64(1),
+                    name: "copy",
+                    fields: vec![
+                        "from=/home/puppy/photos/2024".to_owned(),
+                        "to=/mnt/backup/photos/2024".to_owned(),
+                        "overwrite=false".to_owned(),
+                    ]
+                    .into(),
+                }],
+                textwrap_options: Some((&TextWrapOptionsOwned::new().with_width(60)).into()),
+                field_layout: FieldLayout::Inline,
+                span_field_layout: SpanFieldLayout::OnePerLine,
+                tree_depth: None,
+                timestamp: None,
+            },
+            expect![[r#"
+                • Copying photos
+                  in copy{from=/home/puppy/photos/2024
+                     to=/mnt/backup/photos/2024
+                     overwrite=false}
+            "#]],
+        );
+    }
+
+    #[test]
+    fn test_long_warning() {
+        let styles = LayerStyles::new();
+        check(
+            HumanEvent {
+                is_long: Cell::new(false),
                 style: styles.for_level(Level::WARN),
                 color: ShouldColor::Always,
                 fields: HumanFields {
@@ -341,12 +1082,16 @@ mod tests {
                         .to_owned(),
                     ),
                     fields: vec![],
+                    quote_strings: QuoteStrings::WhenNeeded,
                 },
                 spans: vec![],
                 textwrap_options: Some((&TextWrapOptionsOwned::new()).into()),
+                field_layout: FieldLayout::Inline,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
             },
             expect![[r#"
-
                 [33m⚠ [0m[33m`nix doctor` found potential issues with your Nix installation:
                   Running checks against store uri: daemon
                   [FAIL] Multiple versions of nix found in PATH:
@@ -356,7 +1101,6 @@ mod tests {
                   [PASS] All profiles are gcroots.
                   [PASS] Client protocol matches store protocol.
                   [0m
-
             "#]],
         );
     }
@@ -366,16 +1110,21 @@ mod tests {
         let styles = LayerStyles::new();
         check(
             HumanEvent {
-                last_event_was_long: AtomicBool::new(false),
+                is_long: Cell::new(false),
                 style: styles.for_level(Level::TRACE),
                 color: ShouldColor::Always,
                 fields: HumanFields {
                     extract_message: true,
                     message: Some("Fine-grained tracing info".to_owned()),
-                    fields: vec![("favorite_doggy_sound", "awooooooo".to_owned())],
+                    fields: vec![("favorite_doggy_sound", "awooooooo".into())],
+                    quote_strings: QuoteStrings::WhenNeeded,
                 },
                 spans: vec![],
                 textwrap_options: Some((&TextWrapOptionsOwned::new()).into()),
+                field_layout: FieldLayout::Inline,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
             },
             expect![[r#"
                 [35mTRACE [0m[2mFine-grained tracing info [1;2mfavorite_doggy_sound[0m[2m=[0m[2mawooooooo[0m[0m
@@ -388,16 +1137,21 @@ mod tests {
         let styles = LayerStyles::new();
         check(
             HumanEvent {
-                last_event_was_long: AtomicBool::new(false),
+                is_long: Cell::new(false),
                 style: styles.for_level(Level::DEBUG),
                 color: ShouldColor::Always,
                 fields: HumanFields {
                     extract_message: true,
                     message: Some("Debugging info".to_owned()),
-                    fields: vec![("puppy", "pawbeans".to_owned())],
+                    fields: vec![("puppy", "pawbeans".into())],
+                    quote_strings: QuoteStrings::WhenNeeded,
                 },
                 spans: vec![],
                 textwrap_options: Some((&TextWrapOptionsOwned::new()).into()),
+                field_layout: FieldLayout::Inline,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
             },
             expect![[r#"
                 [34mDEBUG [0m[2mDebugging info [1;2mpuppy[0m[2m=[0m[2mpawbeans[0m[0m
@@ -410,7 +1164,7 @@ mod tests {
         let styles = LayerStyles::new();
         check(
             HumanEvent {
-                last_event_was_long: AtomicBool::new(false),
+                is_long: Cell::new(false),
                 style: styles.for_level(Level::WARN),
                 color: ShouldColor::Always,
                 fields: HumanFields {
@@ -419,19 +1173,134 @@ mod tests {
                         Note that access to Mercury repositories on GitHub over SSH is required to enter the `nix develop` shell in `mercury-web-backend`\n\
                         See: https://docs.github.com/en/authentication/connecting-to-github-with-ssh/adding-a-new-ssh-key-to-your-github-account".to_owned()),
                     fields: vec![],
+                    quote_strings: QuoteStrings::WhenNeeded,
                 },
                 spans: vec![],
                 textwrap_options: Some((&TextWrapOptionsOwned::new()).into()),
+                field_layout: FieldLayout::Inline,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
             },
             expect![[r#"
-
                 [33m⚠ [0m[33mI was unable to clone `mercury-web-backend`; most likely this is because you
                   don't have a proper SSH key available.
                   Note that access to Mercury repositories on GitHub over SSH is required to
                   enter the `nix develop` shell in `mercury-web-backend`
                   See:
                   https://docs.github.com/en/authentication/connecting-to-github-with-ssh/adding-a-new-ssh-key-to-your-github-account[0m
+            "#]],
+        );
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let styles = LayerStyles::new();
+        check(
+            HumanEvent {
+                is_long: Cell::new(false),
+                style: styles.for_level(Level::INFO),
+                color: ShouldColor::Always,
+                fields: HumanFields {
+                    extract_message: true,
+                    message: Some("Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua.".to_owned()),
+                    fields: vec![
+                        ("path", "~/.config/nix/nix.conf".into()),
+                        ("user", "puppy".into()),
+                    ],
+                    quote_strings: QuoteStrings::WhenNeeded,
+                },
+                spans: vec![SpanInfo {
+                    id: Id::from_u64(1),
+                    name: "copy",
+                    fields: Default::default(),
+                }],
+                textwrap_options: Some((&TextWrapOptionsOwned::new()).into()),
+                field_layout: FieldLayout::Inline,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: Some("+12.345s".to_owned()),
+            },
+            expect![[r#"
+                [2m+12.345s[0m [32m• [0mLorem ipsum dolor sit amet, consectetur adipiscing elit, sed do
+                           eiusmod tempor incididunt ut labore et dolore magna aliqua.
+                           [1mpath[0m=~/.config/nix/nix.conf
+                           [1muser[0m=puppy
+                           [2min [0mcopy
+            "#]],
+        );
+    }
 
+    #[test]
+    fn test_timestamp_no_wrap() {
+        let styles = LayerStyles::new();
+        check(
+            HumanEvent {
+                is_long: Cell::new(false),
+                style: styles.for_level(Level::INFO),
+                color: ShouldColor::Never,
+                fields: HumanFields {
+                    extract_message: true,
+                    message: Some("Info event.".to_owned()),
+                    fields: vec![("path", "~/.config/nix/nix.conf".into())],
+                    quote_strings: QuoteStrings::WhenNeeded,
+                },
+                spans: vec![SpanInfo {
+                    id: Id::from_u64(1),
+                    name: "copy",
+                    fields: Default::default(),
+                }],
+                textwrap_options: None,
+                field_layout: FieldLayout::Inline,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: Some("14:03:27.123".to_owned()),
+            },
+            expect![[r#"
+                14:03:27.123 • Info event. path=~/.config/nix/nix.conf
+                               in copy
+            "#]],
+        );
+    }
+
+    #[test]
+    fn test_error_sources() {
+        let styles = LayerStyles::new();
+        check(
+            HumanEvent {
+                is_long: Cell::new(false),
+                style: styles.for_level(Level::ERROR),
+                color: ShouldColor::Always,
+                fields: HumanFields {
+                    extract_message: true,
+                    message: Some("Failed to start".to_owned()),
+                    fields: vec![(
+                        "error",
+                        FieldValue::Error {
+                            message: "Failed to read configuration from any of the directories in `XDG_CONFIG_DIRS`".to_owned(),
+                            sources: vec![
+                                "Failed to open `/Users/wiggles/.config/my-cool-program/config.toml` for reading".to_owned(),
+                                "No such file or directory (os error 2)".to_owned(),
+                            ],
+                        },
+                    )],
+                    quote_strings: QuoteStrings::WhenNeeded,
+                },
+                spans: vec![],
+                textwrap_options: Some((&TextWrapOptionsOwned::new()).into()),
+                field_layout: FieldLayout::Inline,
+                span_field_layout: SpanFieldLayout::Wrap,
+                tree_depth: None,
+                timestamp: None,
+            },
+            expect![[r#"
+                [31m⚠ [0m[31mFailed to start[0m
+                  [1merror[0m=Failed to read configuration from any of the directories in
+                    `XDG_CONFIG_DIRS`
+                    [2mcaused by:[0m
+                    [2m1. [0mFailed to open `/Users/wiggles/.config/my-cool-program/config.toml` for
+                       reading
+                    [2m2. [0mNo such file or directory (os error 2)
             "#]],
         );
     }
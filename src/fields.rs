@@ -1,61 +1,246 @@
+use std::error::Error;
 use std::fmt;
 use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Write;
 
 use tracing::field::Field;
+use tracing::field::FieldSet;
 use tracing::field::Visit;
 
+use textwrap::core::display_width;
+
 /// Formatted fields on a span or event.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HumanFields {
     pub extract_message: bool,
     pub message: Option<String>,
-    pub fields: Vec<(&'static str, String)>,
+    pub fields: Vec<(&'static str, FieldValue)>,
+    pub quote_strings: QuoteStrings,
+}
+
+/// When to quote string field values.
+///
+/// See [`crate::HumanLayer::with_quote_strings`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuoteStrings {
+    /// Quote strings which are empty or contain whitespace, `=`, or `"`, like `path="a b"` and
+    /// `path=a/b`.
+    #[default]
+    WhenNeeded,
+    /// Never quote strings, like `path=a b`.
+    Never,
+    /// Always quote strings, like `path="a/b"`.
+    Always,
+}
+
+impl QuoteStrings {
+    fn should_quote(self, value: &str) -> bool {
+        match self {
+            QuoteStrings::WhenNeeded => {
+                value.is_empty()
+                    || value
+                        .chars()
+                        .any(|c| c.is_whitespace() || c == '=' || c == '"')
+            }
+            QuoteStrings::Never => false,
+            QuoteStrings::Always => true,
+        }
+    }
+}
+
+/// How to lay out an event's fields.
+///
+/// See [`crate::HumanLayer::with_field_layout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FieldLayout {
+    /// Put as many fields as fit on the same line as the message, and the rest on separate
+    /// lines, like `Copying path=a.txt size=12`.
+    #[default]
+    Inline,
+    /// Put each field on a separate line.
+    OnePerLine,
+    /// Put each field on a separate line, with the `=` signs lined up:
+    ///
+    /// ```text
+    /// • Copying
+    ///   path =a.txt
+    ///   size =12
+    ///   owner=puppy
+    /// ```
+    Aligned,
+}
+
+/// The value of a field on a span or event.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    /// A value formatted with [`Debug`].
+    Debug(String),
+    /// A string, which may be quoted depending on the [`QuoteStrings`] policy.
+    Str(String),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Bool(bool),
+    /// An error, formatted with [`Display`], and the [`Display`] of each of its
+    /// [`Error::source`]s.
+    Error {
+        message: String,
+        sources: Vec<String>,
+    },
+}
+
+impl FieldValue {
+    /// Does this value need more than one line in the long format?
+    pub fn is_multiline(&self) -> bool {
+        match self {
+            FieldValue::Str(value) => value.contains('\n'),
+            FieldValue::Error { sources, .. } => !sources.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Display this value on one line (except for strings containing newlines).
+    ///
+    /// Errors are written like `message: source: source`.
+    pub fn display(&self, quote_strings: QuoteStrings) -> DisplayFieldValue<'_> {
+        DisplayFieldValue {
+            value: self,
+            quote_strings,
+        }
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(value: String) -> Self {
+        Self::Str(value)
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(value: &str) -> Self {
+        Self::Str(value.to_owned())
+    }
+}
+
+/// A [`FieldValue`] formatted according to a [`QuoteStrings`] policy.
+pub struct DisplayFieldValue<'a> {
+    value: &'a FieldValue,
+    quote_strings: QuoteStrings,
+}
+
+impl Display for DisplayFieldValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value {
+            FieldValue::Debug(value) => f.write_str(value),
+            FieldValue::Str(value) => {
+                if self.quote_strings.should_quote(value) {
+                    // Escape quotes and backslashes so the value is unambiguous, but leave
+                    // newlines and other characters alone so the value stays readable.
+                    f.write_char('"')?;
+                    for c in value.chars() {
+                        if c == '"' || c == '\\' {
+                            f.write_char('\\')?;
+                        }
+                        f.write_char(c)?;
+                    }
+                    f.write_char('"')
+                } else {
+                    f.write_str(value)
+                }
+            }
+            FieldValue::I64(value) => write!(f, "{value}"),
+            FieldValue::U64(value) => write!(f, "{value}"),
+            // `Debug` keeps the `.0` on whole numbers.
+            FieldValue::F64(value) => write!(f, "{value:?}"),
+            FieldValue::Bool(value) => write!(f, "{value}"),
+            FieldValue::Error { message, sources } => {
+                f.write_str(message)?;
+                for source in sources {
+                    write!(f, ": {source}")?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 impl HumanFields {
-    pub fn new_event() -> Self {
+    pub fn new_event(quote_strings: QuoteStrings) -> Self {
         Self {
             extract_message: true,
             message: Default::default(),
             fields: Default::default(),
+            quote_strings,
         }
     }
 
-    pub fn new_span() -> Self {
+    pub fn new_span(quote_strings: QuoteStrings) -> Self {
         Self {
             extract_message: false,
             message: Default::default(),
             fields: Default::default(),
+            quote_strings,
         }
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.message.is_none() && self.fields.is_empty()
-    }
+    /// How many fields to put on the same line as the message, in the given [`FieldLayout`].
+    /// The rest of the fields are written in the 'long format', with each field on a separate
+    /// line.
+    ///
+    /// `width` is the width available for the message and fields, after the indent, or `None`
+    /// if text isn't wrapped. Widths are measured on the rendered text.
+    pub fn inline_fields(&self, layout: FieldLayout, width: Option<usize>) -> usize {
+        if layout != FieldLayout::Inline {
+            return 0;
+        }
+        let Some(width) = width else {
+            return self.fields.len();
+        };
 
-    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
-        self.message
-            .iter()
-            .map(|message| ("message", message.as_str()))
-            .chain(
-                self.fields
-                    .iter()
-                    .map(|(name, value)| (*name, value.as_str())),
-            )
+        let mut used = self.message.as_deref().map_or(0, display_width);
+        let mut count = 0;
+        for (name, value) in &self.fields {
+            if value.is_multiline() {
+                break;
+            }
+            // A space, then `name=value`.
+            let field_width = 1
+                + display_width(name)
+                + 1
+                + display_width(&value.display(self.quote_strings).to_string());
+            if used + field_width > width {
+                break;
+            }
+            used += field_width;
+            count += 1;
+        }
+        count
     }
 
-    /// If there's only one field, and it fits on the same line as the message, put it on the
-    /// same line. Otherwise, we use the 'long format' with each field on a separate line.
-    pub fn use_short_format(&self, term_width: usize) -> bool {
-        self.fields.len() == 1
-            && self.fields[0].0.len() + self.fields[0].1.len() + 2
-                < term_width
-                    .saturating_sub(self.message.as_ref().map_or(0, |message| message.len()))
+    /// Merge fields recorded later (with [`tracing::Span::record`]) into these fields.
+    ///
+    /// Fields which were already recorded are replaced, and the fields are kept in the order
+    /// they're declared in the `field_set`, so fields declared with [`tracing::field::Empty`]
+    /// show up where they were declared.
+    pub fn merge(&mut self, recorded: HumanFields, field_set: &FieldSet) {
+        for (name, value) in recorded.fields {
+            match self
+                .fields
+                .iter_mut()
+                .find(|(existing, _)| *existing == name)
+            {
+                Some((_, existing)) => *existing = value,
+                None => self.fields.push((name, value)),
+            }
+        }
+        self.fields
+            .sort_by_key(|(name, _)| field_set.field(name).map(|field| field.index()));
     }
 
-    pub fn record_field(&mut self, field_name: &'static str, value: String) {
+    pub fn record_field(&mut self, field_name: &'static str, value: FieldValue) {
         if self.extract_message && field_name == "message" {
-            self.message = Some(value);
+            self.message = Some(value.display(QuoteStrings::Never).to_string());
         } else {
             self.fields.push((field_name, value));
         }
@@ -64,6 +249,133 @@ impl HumanFields {
 
 impl Visit for HumanFields {
     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
-        self.record_field(field.name(), format!("{value:?}"))
+        self.record_field(field.name(), FieldValue::Debug(format!("{value:?}")))
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record_field(field.name(), FieldValue::Str(value.to_owned()))
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record_field(field.name(), FieldValue::I64(value))
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record_field(field.name(), FieldValue::U64(value))
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.record_field(field.name(), FieldValue::F64(value))
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.record_field(field.name(), FieldValue::Bool(value))
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn Error + 'static)) {
+        let mut sources = Vec::new();
+        let mut source = value.source();
+        while let Some(error) = source {
+            sources.push(error.to_string());
+            source = error.source();
+        }
+        self.record_field(
+            field.name(),
+            FieldValue::Error {
+                message: value.to_string(),
+                sources,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(value: impl Into<FieldValue>, quote_strings: QuoteStrings) -> String {
+        value.into().display(quote_strings).to_string()
+    }
+
+    #[test]
+    fn test_quote_when_needed() {
+        let quote = QuoteStrings::WhenNeeded;
+        assert_eq!(display("a/b", quote), "a/b");
+        assert_eq!(display("a b", quote), r#""a b""#);
+        assert_eq!(display("a=b", quote), r#""a=b""#);
+        assert_eq!(display("", quote), r#""""#);
+        assert_eq!(display(r#"say "hi""#, quote), r#""say \"hi\"""#);
+        assert_eq!(display("a\nb", quote), "\"a\nb\"");
+    }
+
+    #[test]
+    fn test_quote_never_always() {
+        assert_eq!(display("a b", QuoteStrings::Never), "a b");
+        assert_eq!(display("a/b", QuoteStrings::Always), r#""a/b""#);
+        assert_eq!(display(r"C:\dir", QuoteStrings::Always), r#""C:\\dir""#);
+    }
+
+    fn event_fields(message: &str, fields: &[(&'static str, &str)]) -> HumanFields {
+        HumanFields {
+            message: Some(message.to_owned()),
+            fields: fields
+                .iter()
+                .map(|(name, value)| (*name, FieldValue::from(*value)))
+                .collect(),
+            ..HumanFields::new_event(QuoteStrings::WhenNeeded)
+        }
+    }
+
+    #[test]
+    fn test_inline_fields() {
+        let fields = event_fields("Copying", &[("a", "1"), ("b", "2"), ("c", "3")]);
+        // `Copying a=1 b=2 c=3` is 19 columns wide.
+        assert_eq!(fields.inline_fields(FieldLayout::Inline, Some(19)), 3);
+        assert_eq!(fields.inline_fields(FieldLayout::Inline, Some(18)), 2);
+        assert_eq!(fields.inline_fields(FieldLayout::Inline, Some(7)), 0);
+        assert_eq!(fields.inline_fields(FieldLayout::Inline, None), 3);
+        assert_eq!(fields.inline_fields(FieldLayout::OnePerLine, None), 0);
+        assert_eq!(fields.inline_fields(FieldLayout::Aligned, Some(80)), 0);
+    }
+
+    #[test]
+    fn test_inline_fields_rendered_width() {
+        // Quotes count, and so do characters rather than bytes.
+        let fields = event_fields("Copying", &[("path", "a b"), ("name", "ééé")]);
+        // `Copying path="a b" name=ééé` is 27 columns wide.
+        assert_eq!(fields.inline_fields(FieldLayout::Inline, Some(27)), 2);
+        assert_eq!(fields.inline_fields(FieldLayout::Inline, Some(26)), 1);
+
+        // Multiline values, and the fields after them, are never inline.
+        let fields = event_fields("Copying", &[("a", "1"), ("b", "x\ny"), ("c", "3")]);
+        assert_eq!(fields.inline_fields(FieldLayout::Inline, Some(80)), 1);
+    }
+
+    #[test]
+    fn test_inline_fields_wide_characters() {
+        // `日本語` is 3 characters but 6 columns wide.
+        let fields = event_fields("Copying", &[("a", "日本語"), ("b", "2")]);
+        // `Copying a=日本語 b=2` is 20 columns wide.
+        assert_eq!(fields.inline_fields(FieldLayout::Inline, Some(20)), 2);
+        assert_eq!(fields.inline_fields(FieldLayout::Inline, Some(19)), 1);
+
+        // Emoji are 2 columns wide, and combining marks take no columns.
+        let fields = event_fields("🦀", &[("name", "cafe\u{301}")]);
+        // `🦀 name=café` is 12 columns wide.
+        assert_eq!(fields.inline_fields(FieldLayout::Inline, Some(12)), 1);
+        assert_eq!(fields.inline_fields(FieldLayout::Inline, Some(11)), 0);
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let quote = QuoteStrings::Always;
+        assert_eq!(display(FieldValue::I64(-1), quote), "-1");
+        assert_eq!(display(FieldValue::U64(2), quote), "2");
+        assert_eq!(display(FieldValue::F64(1.0), quote), "1.0");
+        assert_eq!(display(FieldValue::Bool(true), quote), "true");
+        assert_eq!(
+            display(FieldValue::Debug(r#""debug""#.to_owned()), quote),
+            r#""debug""#
+        );
     }
 }
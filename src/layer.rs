@@ -1,9 +1,15 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::cell::RefCell;
 use std::fmt::Debug;
+use std::fmt::Write as _;
+use std::io;
 use std::io::LineWriter;
 use std::io::Stderr;
-use std::io::Write;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
 
 use parking_lot::Mutex;
 use tracing::span;
@@ -12,43 +18,103 @@ use tracing::Id;
 use tracing::Metadata;
 use tracing::Subscriber;
 use tracing_subscriber::fmt::format::FmtSpan;
-use tracing_subscriber::fmt::FormattedFields;
+use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::layer::Context;
 use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::registry::Scope;
 use tracing_subscriber::Layer;
 
+use crate::strip_ansi;
+use crate::Destination;
+use crate::EventSpacing;
+use crate::FieldLayout;
+use crate::FormattedFields;
 use crate::HumanEvent;
 use crate::HumanFields;
+use crate::LayerId;
 use crate::LayerStyles;
+use crate::MakeWriterOutput;
+use crate::MaybeTerminal;
+use crate::Output;
+use crate::OutputWriter;
+use crate::Progress;
 use crate::ProvideStyle;
+use crate::QuoteStrings;
 use crate::ShouldColor;
+use crate::SpanDisplay;
+use crate::SpanFieldLayout;
+use crate::SpanFields;
+use crate::SpanFieldsByLayer;
 use crate::SpanInfo;
-use crate::StyledSpanFields;
+use crate::SpanTimingThresholds;
+use crate::SpanTimings;
 use crate::TextWrapOptionsOwned;
+use crate::TimestampFormat;
+use crate::Timestamps;
+use crate::UnchangedSpans;
+use crate::WriteErrorPolicy;
 
 #[cfg(doc)]
 use crate::Style;
 
+/// Chooses which events are written to a [`Destination`]. See [`HumanLayer::with_route`].
+type RouteFilter = Box<dyn Fn(&Metadata<'_>) -> bool + Send + Sync>;
+
 /// A human-friendly [`tracing_subscriber::Layer`].
 pub struct HumanLayer<W = LineWriter<Stderr>, S = LayerStyles> {
-    /// We print blank lines before and after long log messages to help visually separate them.
-    ///
-    /// This becomes an issue if two long log messages are printed one after another.
-    ///
-    /// If this variable is `true`, we skip the blank line before to prevent printing two blank
-    /// lines in a row.
-    ///
-    /// This variable is mutated whenever a [`HumanEvent`] is displayed.
-    last_event_was_long: AtomicBool,
+    /// Identifies this layer's formatted fields in span extensions, so that several layers can
+    /// be used in one subscriber.
+    id: LayerId,
+    /// When to print blank lines between events.
+    event_spacing: EventSpacing,
     /// Which span events to emit.
     span_events: FmtSpan,
-    /// Whether to color the output.
+    /// When to show timing information for [`FmtSpan::CLOSE`] events.
+    span_timing_thresholds: SpanTimingThresholds,
+    /// Whether to emit an event when a span's fields are recorded.
+    span_record_events: bool,
+    /// Whether to color the output, as configured.
     color_output: ShouldColor,
+    /// Whether the `output_writer` is connected to a terminal.
+    output_is_terminal: bool,
+    /// Whether to color the output, with [`ShouldColor::Auto`] resolved for the
+    /// `output_writer`.
+    color: ShouldColor,
     /// Options for wrapping text, if any.
     textwrap_options: Option<TextWrapOptionsOwned>,
+    /// When to quote string field values.
+    quote_strings: QuoteStrings,
+    /// How to lay out event fields.
+    field_layout: FieldLayout,
+    /// How to lay out span fields which don't fit on one line.
+    span_field_layout: SpanFieldLayout,
+    /// What to write instead of an event's spans when they're unchanged.
+    unchanged_spans: UnchangedSpans,
+    /// Whether to show spans as a tree, indenting events by their span depth.
+    span_tree: bool,
+    /// Whether to show progress for spans with progress fields.
+    progress: bool,
+    /// How often to write progress as plain lines, if the output isn't a terminal.
+    progress_interval: Duration,
+    /// Whether to show the spans which are currently entered below the other output.
+    status_footer: bool,
+    /// How long spans must run before they're shown in the status footer.
+    status_footer_threshold: Duration,
+    /// Timestamps to print before each event, if any.
+    timestamps: Option<Timestamps>,
+    /// When the layer was constructed, for [`TimestampFormat::Uptime`] timestamps.
+    created: Instant,
+    /// What to do when writing output fails.
+    write_error_policy: WriteErrorPolicy,
+    /// Set when writing output fails and the `write_error_policy` says to stop.
+    disabled: AtomicBool,
     /// The writer where output is written.
-    output_writer: Mutex<W>,
+    output_writer: Mutex<Output<W>>,
+    /// Events written to other destinations instead of the `output_writer`, along with the
+    /// filters which choose them.
+    routes: Vec<(RouteFilter, Destination)>,
+    /// Destinations which every event is also written to.
+    tees: Vec<Destination>,
     /// Styles for writing events.
     styles: S,
 }
@@ -57,8 +123,35 @@ impl<W, S> Debug for HumanLayer<W, S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("HumanLayer")
             .field("span_events", &self.span_events)
+            .field("span_timing_thresholds", &self.span_timing_thresholds)
+            .field("span_record_events", &self.span_record_events)
             .field("color_output", &self.color_output)
+            .field("color", &self.color)
             .field("textwrap_options", &self.textwrap_options)
+            .field("quote_strings", &self.quote_strings)
+            .field("field_layout", &self.field_layout)
+            .field("span_field_layout", &self.span_field_layout)
+            .field("unchanged_spans", &self.unchanged_spans)
+            .field("span_tree", &self.span_tree)
+            .field("progress", &self.progress)
+            .field("progress_interval", &self.progress_interval)
+            .field("status_footer", &self.status_footer)
+            .field("status_footer_threshold", &self.status_footer_threshold)
+            .field(
+                "routes",
+                &self
+                    .routes
+                    .iter()
+                    .map(|(_, destination)| destination)
+                    .collect::<Vec<_>>(),
+            )
+            .field("tees", &self.tees)
+            .field("write_error_policy", &self.write_error_policy)
+            .field("event_spacing", &self.event_spacing)
+            .field(
+                "timestamps",
+                &self.timestamps.as_ref().map(Timestamps::format),
+            )
             // These strings get debug-formatted, which is a bit ugly, but it's fine.
             // See: https://github.com/rust-lang/rust/issues/117729
             .field("output_writer", &std::any::type_name::<W>())
@@ -70,12 +163,32 @@ impl<W, S> Debug for HumanLayer<W, S> {
 impl Default for HumanLayer {
     fn default() -> Self {
         Self {
-            last_event_was_long: Default::default(),
+            id: LayerId::new(),
+            event_spacing: EventSpacing::default(),
             span_events: FmtSpan::NONE,
+            span_timing_thresholds: SpanTimingThresholds::new(),
+            span_record_events: false,
             color_output: ShouldColor::Always,
-            output_writer: Mutex::new(LineWriter::new(std::io::stderr())),
+            output_is_terminal: std::io::stderr().is_terminal(),
+            color: ShouldColor::Always,
+            output_writer: Mutex::new(Output::new(LineWriter::new(std::io::stderr()))),
+            routes: Vec::new(),
+            tees: Vec::new(),
             styles: LayerStyles::new(),
             textwrap_options: Some(TextWrapOptionsOwned::new()),
+            quote_strings: QuoteStrings::WhenNeeded,
+            field_layout: FieldLayout::default(),
+            span_field_layout: SpanFieldLayout::default(),
+            unchanged_spans: UnchangedSpans::default(),
+            span_tree: false,
+            progress: false,
+            progress_interval: Duration::from_secs(1),
+            status_footer: false,
+            status_footer_threshold: Duration::ZERO,
+            timestamps: None,
+            created: Instant::now(),
+            write_error_policy: WriteErrorPolicy::default(),
+            disabled: AtomicBool::new(false),
         }
     }
 }
@@ -90,21 +203,195 @@ impl HumanLayer {
 impl<W, S> HumanLayer<W, S> {
     /// Set the writer that log messages are written to.
     ///
-    /// This does not change colored output by default.
+    /// The writer is assumed not to be a terminal, so if the output coloring is
+    /// [`ShouldColor::Auto`], the output isn't colored, and progress bars and the status footer
+    /// aren't drawn. Use [`HumanLayer::with_output_writer_detect`] to check whether the writer is
+    /// a terminal, or [`HumanLayer::with_output_writer_terminal`] to say so explicitly.
     ///
-    /// The `output_writer` should implement [`std::io::Write`] for the [`HumanLayer`] to
-    /// implement [`tracing_subscriber::Layer`].
+    /// The `output_writer` should implement [`std::io::Write`] (or, more precisely,
+    /// [`OutputWriter`]) for the [`HumanLayer`] to implement [`tracing_subscriber::Layer`].
     pub fn with_output_writer<W2>(self, output_writer: W2) -> HumanLayer<W2, S> {
+        self.with_output_writer_terminal(output_writer, false)
+    }
+
+    /// Set the writer that log messages are written to, using [`MaybeTerminal`] to check
+    /// whether it's connected to a terminal.
+    ///
+    /// If the output coloring is [`ShouldColor::Auto`], it's re-evaluated for the new writer:
+    ///
+    /// ```
+    /// # use tracing_human_layer::HumanLayer;
+    /// # use tracing_human_layer::ShouldColor;
+    /// let layer = HumanLayer::new()
+    ///     .with_color_output(ShouldColor::Auto)
+    ///     .with_output_writer_detect(std::io::stdout());
+    /// ```
+    pub fn with_output_writer_detect<W2>(self, output_writer: W2) -> HumanLayer<W2, S>
+    where
+        W2: MaybeTerminal,
+    {
+        let output_is_terminal = output_writer.is_terminal();
+        self.with_output_writer_terminal(output_writer, output_is_terminal)
+    }
+
+    /// Set the writer that log messages are written to, and whether it's connected to a
+    /// terminal.
+    ///
+    /// This works for any writer, including ones which don't implement [`MaybeTerminal`]. If
+    /// the output coloring is [`ShouldColor::Auto`], it's re-evaluated for the new writer.
+    ///
+    /// ```
+    /// # use std::net::TcpStream;
+    /// # use tracing_human_layer::HumanLayer;
+    /// # use tracing_human_layer::ShouldColor;
+    /// # fn connect() -> std::io::Result<()> {
+    /// let stream = TcpStream::connect("127.0.0.1:9000")?;
+    /// let layer = HumanLayer::new()
+    ///     .with_color_output(ShouldColor::Auto)
+    ///     .with_output_writer_terminal(stream, false);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_output_writer_terminal<W2>(
+        self,
+        output_writer: W2,
+        output_is_terminal: bool,
+    ) -> HumanLayer<W2, S> {
         HumanLayer {
-            last_event_was_long: self.last_event_was_long,
+            id: self.id,
+            event_spacing: self.event_spacing,
             span_events: self.span_events,
+            span_timing_thresholds: self.span_timing_thresholds,
+            span_record_events: self.span_record_events,
             color_output: self.color_output,
-            output_writer: Mutex::new(output_writer),
+            output_is_terminal,
+            color: self.color_output.resolve(output_is_terminal),
+            output_writer: Mutex::new(Output::new(output_writer)),
+            routes: self.routes,
+            tees: self.tees,
             styles: self.styles,
             textwrap_options: self.textwrap_options,
+            quote_strings: self.quote_strings,
+            field_layout: self.field_layout,
+            span_field_layout: self.span_field_layout,
+            unchanged_spans: self.unchanged_spans,
+            span_tree: self.span_tree,
+            progress: self.progress,
+            progress_interval: self.progress_interval,
+            status_footer: self.status_footer,
+            status_footer_threshold: self.status_footer_threshold,
+            timestamps: self.timestamps,
+            created: self.created,
+            write_error_policy: self.write_error_policy,
+            disabled: self.disabled,
         }
     }
 
+    /// Write the events chosen by `filter` to the `destination` instead of the output writer.
+    ///
+    /// Routes are checked in the order they're added, and each event is written to the first
+    /// destination which matches it. Span events are routed by the span's metadata. Progress
+    /// bars and the status footer are only drawn on the output writer.
+    ///
+    /// For example, to write warnings and errors to stderr and everything else to stdout:
+    ///
+    /// ```
+    /// # use std::io::LineWriter;
+    /// # use tracing::Level;
+    /// # use tracing_human_layer::Destination;
+    /// # use tracing_human_layer::HumanLayer;
+    /// # use tracing_human_layer::ShouldColor;
+    /// let layer = HumanLayer::new()
+    ///     .with_color_output(ShouldColor::Auto)
+    ///     .with_route(
+    ///         |metadata| *metadata.level() > Level::WARN,
+    ///         Destination::new(LineWriter::new(std::io::stdout()))
+    ///             .with_color_output(ShouldColor::Auto),
+    ///     );
+    /// ```
+    pub fn with_route(
+        mut self,
+        filter: impl Fn(&Metadata<'_>) -> bool + Send + Sync + 'static,
+        destination: Destination,
+    ) -> Self {
+        self.routes.push((Box::new(filter), destination));
+        self
+    }
+
+    /// Also write every event to the `destination`, formatted with its own color and text
+    /// wrapping options.
+    ///
+    /// Events are only recorded once, no matter how many places they're written. For example,
+    /// to keep an uncolored and unwrapped copy of the output in a log file:
+    ///
+    /// ```no_run
+    /// # use std::fs::File;
+    /// # use std::io::BufWriter;
+    /// # use tracing_human_layer::Destination;
+    /// # use tracing_human_layer::HumanLayer;
+    /// let log = File::options().create(true).append(true).open("log.txt")?;
+    /// let layer = HumanLayer::new().with_tee(
+    ///     Destination::new(BufWriter::new(log))
+    ///         .with_color_output(false)
+    ///         .with_textwrap_options(None),
+    /// );
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn with_tee(mut self, destination: Destination) -> Self {
+        self.tees.push(destination);
+        self
+    }
+
+    /// The destination an event is routed to, if it isn't written to the output writer.
+    fn route(&self, metadata: &Metadata<'_>) -> Option<&Destination> {
+        self.routes
+            .iter()
+            .find(|(filter, _)| filter(metadata))
+            .map(|(_, destination)| destination)
+    }
+
+    /// The colors which span fields are written in, for the output writer, each route, and
+    /// each tee.
+    fn colors(&self) -> impl Iterator<Item = ShouldColor> + '_ {
+        std::iter::once(self.color)
+            .chain(self.routes.iter().map(|(_, destination)| destination.color))
+            .chain(self.tees.iter().map(|destination| destination.color))
+    }
+
+    /// Write output to writers made by a [`MakeWriter`] instead of a single output writer.
+    ///
+    /// A writer is made for each event with [`MakeWriter::make_writer_for`]. This makes it
+    /// possible to use [`tracing_subscriber::fmt::TestWriter`], so that output is captured
+    /// along with each test's output:
+    ///
+    /// ```
+    /// # use tracing_human_layer::HumanLayer;
+    /// let layer = HumanLayer::new().with_make_writer(tracing_subscriber::fmt::TestWriter::new());
+    /// ```
+    ///
+    /// A [`MakeWriter`] can't tell whether its output is a terminal, so [`ShouldColor::Auto`]
+    /// doesn't color the output, and progress bars and the status footer aren't drawn.
+    pub fn with_make_writer<M>(self, make_writer: M) -> HumanLayer<MakeWriterOutput<M>, S>
+    where
+        M: for<'a> MakeWriter<'a>,
+    {
+        self.with_output_writer(MakeWriterOutput::new(make_writer))
+    }
+
+    /// Set what to do when writing output fails, like when the output is a closed pipe or a
+    /// full disk.
+    ///
+    /// By default, errors are ignored.
+    pub fn with_write_error_policy(mut self, write_error_policy: WriteErrorPolicy) -> Self {
+        self.write_error_policy = write_error_policy;
+        self
+    }
+
+    /// Has writing output failed in a way that means we should stop?
+    fn is_disabled(&self) -> bool {
+        self.disabled.load(Ordering::Relaxed)
+    }
+
     /// Set the [`textwrap::Options`].
     ///
     /// If `None`, no text wrapping is performed.
@@ -113,39 +400,195 @@ impl<W, S> HumanLayer<W, S> {
         self
     }
 
+    /// Set when string field values are quoted.
+    ///
+    /// By default, strings are only quoted when they're empty or contain whitespace, `=`, or `"`.
+    pub fn with_quote_strings(mut self, quote_strings: QuoteStrings) -> Self {
+        self.quote_strings = quote_strings;
+        self
+    }
+
+    /// Set how event fields are laid out.
+    ///
+    /// By default, as many fields as fit are put on the same line as the message.
+    pub fn with_field_layout(mut self, field_layout: FieldLayout) -> Self {
+        self.field_layout = field_layout;
+        self
+    }
+
+    /// Set how span fields are laid out when they don't fit on one line.
+    ///
+    /// By default, lines are broken between fields, with as many fields as fit on each line.
+    pub fn with_span_field_layout(mut self, span_field_layout: SpanFieldLayout) -> Self {
+        self.span_field_layout = span_field_layout;
+        self
+    }
+
+    /// Set what to write instead of an event's spans when they're the same as the previous
+    /// event's spans.
+    ///
+    /// By default, the spans are written for every event.
+    pub fn with_unchanged_spans(mut self, unchanged_spans: UnchangedSpans) -> Self {
+        self.unchanged_spans = unchanged_spans;
+        self
+    }
+
+    /// Show spans as a tree.
+    ///
+    /// When a span is first entered, its name and fields are written as a header. Events in a
+    /// span are indented with one guide for each span they're in (see
+    /// [`Style::with_tree_guide_text`]), instead of listing their spans after them. Span
+    /// events, like the [`FmtSpan::CLOSE`] events which report how long a span took, are
+    /// indented to the span's header.
+    pub fn with_span_tree(mut self, span_tree: bool) -> Self {
+        self.span_tree = span_tree;
+        self
+    }
+
+    /// Show progress for spans with `progress.current` and `progress.total` fields, updated
+    /// with [`tracing::Span::record`]:
+    ///
+    /// ```
+    /// let span = tracing::info_span!(
+    ///     "download",
+    ///     progress.current = 0,
+    ///     progress.total = 100,
+    /// );
+    /// span.record("progress.current", 42);
+    /// ```
+    ///
+    /// If the output is a terminal, open spans with progress are drawn as progress bars (or
+    /// spinners, if there's no `progress.total`) below the rest of the output. Otherwise,
+    /// progress is written as plain lines, at most once per
+    /// [`HumanLayer::with_progress_interval`] for each span.
+    pub fn with_progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Set how often progress is written as plain lines when the output isn't a terminal.
+    ///
+    /// By default, progress is written at most once per second for each span.
+    pub fn with_progress_interval(mut self, progress_interval: Duration) -> Self {
+        self.progress_interval = progress_interval;
+        self
+    }
+
+    /// Show the spans which are currently entered below the other output, along with their
+    /// fields and how long they've been running, like `build{crate=foo} 1m 05s`.
+    ///
+    /// The footer is redrawn as spans are entered, exited, and closed, and as events are
+    /// written above it. It's only shown if the output is a terminal.
+    pub fn with_status_footer(mut self, status_footer: bool) -> Self {
+        self.status_footer = status_footer;
+        self
+    }
+
+    /// Set how long spans must run before they're shown in the status footer, so that quick
+    /// spans don't flicker in and out of it.
+    ///
+    /// The footer isn't redrawn on a timer, so a span which has run for long enough only
+    /// appears the next time something else changes, like when an event is written or another
+    /// span is entered. By default, there's no threshold, so spans are shown as soon as they're
+    /// entered.
+    pub fn with_status_footer_threshold(mut self, status_footer_threshold: Duration) -> Self {
+        self.status_footer_threshold = status_footer_threshold;
+        self
+    }
+
+    /// Set when blank lines are printed between events.
+    ///
+    /// By default, blank lines are printed around events with messages longer than one line.
+    pub fn with_event_spacing(mut self, event_spacing: EventSpacing) -> Self {
+        self.event_spacing = event_spacing;
+        self
+    }
+
+    /// Print a timestamp in the given format before each event.
+    ///
+    /// If `None`, no timestamps are printed. Timestamps are styled with
+    /// [`Style::with_timestamp`].
+    pub fn with_timestamps(mut self, timestamps: Option<TimestampFormat>) -> Self {
+        self.timestamps = timestamps.map(|format| Timestamps::new(format, self.created));
+        self
+    }
+
     /// Set the output coloring.
-    pub fn with_color_output(mut self, color_output: bool) -> Self {
-        // TODO: Should we expose `ShouldColor` and take it as a parameter here?
-        self.color_output = if color_output {
-            ShouldColor::Always
-        } else {
-            ShouldColor::Never
-        };
+    ///
+    /// This accepts a [`ShouldColor`] or a [`bool`].
+    pub fn with_color_output(mut self, color_output: impl Into<ShouldColor>) -> Self {
+        self.color_output = color_output.into();
+        self.color = self.color_output.resolve(self.output_is_terminal);
         self
     }
 
     /// Set which span events are logged.
+    ///
+    /// [`FmtSpan::CLOSE`] events report how long the span was open for, depending on the
+    /// [`HumanLayer::with_span_timing_thresholds`].
     pub fn with_span_events(mut self, span_events: FmtSpan) -> Self {
         self.span_events = span_events;
         self
     }
 
+    /// Emit an event when a span's fields are recorded with [`tracing::Span::record`], showing
+    /// the new values.
+    pub fn with_span_record_events(mut self, span_record_events: bool) -> Self {
+        self.span_record_events = span_record_events;
+        self
+    }
+
+    /// Set when timing information is shown for [`FmtSpan::CLOSE`] events.
+    pub fn with_span_timing_thresholds(
+        mut self,
+        span_timing_thresholds: SpanTimingThresholds,
+    ) -> Self {
+        self.span_timing_thresholds = span_timing_thresholds;
+        self
+    }
+
     /// Set the output style to the given [`ProvideStyle`] implementation, which supplies
     /// [`Style`]s.
     pub fn with_style_provider<S2>(self, styles: S2) -> HumanLayer<W, S2> {
         HumanLayer {
-            last_event_was_long: self.last_event_was_long,
+            id: self.id,
+            event_spacing: self.event_spacing,
             span_events: self.span_events,
+            span_timing_thresholds: self.span_timing_thresholds,
+            span_record_events: self.span_record_events,
             color_output: self.color_output,
+            output_is_terminal: self.output_is_terminal,
+            color: self.color,
             output_writer: self.output_writer,
+            routes: self.routes,
+            tees: self.tees,
             styles,
             textwrap_options: self.textwrap_options,
+            quote_strings: self.quote_strings,
+            field_layout: self.field_layout,
+            span_field_layout: self.span_field_layout,
+            unchanged_spans: self.unchanged_spans,
+            span_tree: self.span_tree,
+            progress: self.progress,
+            progress_interval: self.progress_interval,
+            status_footer: self.status_footer,
+            status_footer_threshold: self.status_footer_threshold,
+            timestamps: self.timestamps,
+            created: self.created,
+            write_error_policy: self.write_error_policy,
+            disabled: self.disabled,
         }
     }
+}
 
-    fn update_long(&self, last_event_was_long: AtomicBool) {
-        self.last_event_was_long
-            .store(last_event_was_long.load(Ordering::SeqCst), Ordering::SeqCst);
+impl<W> HumanLayer<W, LayerStyles> {
+    /// Set which spans are listed after events of every level.
+    ///
+    /// To choose different spans for each level, set [`Style::with_span_display`] in the
+    /// [`LayerStyles`] and use [`HumanLayer::with_style_provider`].
+    pub fn with_span_display(mut self, span_display: SpanDisplay) -> Self {
+        self.styles = self.styles.with_span_display(span_display);
+        self
     }
 }
 
@@ -162,30 +605,416 @@ where
         R: tracing::Subscriber,
         R: for<'lookup> LookupSpan<'lookup>,
     {
+        let style = self.styles.for_metadata(metadata);
+        // The spans are kept even if they aren't listed, because they're needed to show the
+        // tree and to find the root span for `EventSpacing::BetweenRootSpans`.
+        let spans = match scope {
+            None => Vec::new(),
+            Some(scope) => SpanInfo::from_scope(scope, self.id),
+        };
+        let tree_depth = self.span_tree.then_some(spans.len());
+        let (color, textwrap_options) = match self.route(metadata) {
+            Some(destination) => (destination.color, &destination.textwrap_options),
+            None => (self.color, &self.textwrap_options),
+        };
         HumanEvent {
-            // Note: We load the value out of our `AtomicBool` and then clone it to create a _new_
-            // `AtomicBool`. After writing an event, we update _our_ `AtomicBool`.
-            last_event_was_long: self.last_event_was_long.load(Ordering::SeqCst).into(),
-            style: self.styles.for_metadata(metadata),
-            color: self.color_output,
-            spans: scope
-                .map(|scope| SpanInfo::from_scope(scope))
-                .unwrap_or_default(),
-            fields: HumanFields::new_event(),
-            textwrap_options: self.textwrap_options.as_ref().map(|options| options.into()),
+            is_long: Default::default(),
+            style,
+            color,
+            spans,
+            fields: HumanFields::new_event(self.quote_strings),
+            field_layout: self.field_layout,
+            span_field_layout: self.span_field_layout,
+            textwrap_options: textwrap_options.as_ref().map(|options| options.into()),
+            tree_depth,
+            timestamp: self.timestamps.as_ref().and_then(Timestamps::now),
+        }
+    }
+
+    fn span_metadata<U>(id: &Id, ctx: &Context<'_, U>) -> &'static Metadata<'static>
+    where
+        U: tracing::Subscriber,
+        U: for<'lookup> LookupSpan<'lookup>,
+    {
+        ctx.metadata(id)
+            .expect("Metadata should exist for the span ID")
+    }
+
+    /// Construct an event for a span, like the events for [`FmtSpan::CLOSE`].
+    ///
+    /// If spans are shown as a tree, the event is indented to the span's header rather than
+    /// inside the span.
+    fn event_for_id<U>(&self, id: &Id, ctx: &Context<'_, U>) -> HumanEvent<'_>
+    where
+        U: tracing::Subscriber,
+        U: for<'lookup> LookupSpan<'lookup>,
+    {
+        let mut human_event = self.event(Self::span_metadata(id, ctx), ctx.span_scope(id));
+        if let Some(depth) = &mut human_event.tree_depth {
+            *depth = depth.saturating_sub(1);
+        }
+        human_event
+    }
+}
+
+impl<W, S> HumanLayer<W, S>
+where
+    S: ProvideStyle,
+{
+    /// Construct the header written when a span is first entered, if spans are shown as a
+    /// tree, like `copy{path=a.txt}`.
+    fn tree_header<U>(&self, id: &Id, ctx: &Context<'_, U>) -> HumanEvent<'_>
+    where
+        U: tracing::Subscriber,
+        U: for<'lookup> LookupSpan<'lookup>,
+    {
+        let mut human_event = self.event_for_id(id, ctx);
+        let mut header = ctx
+            .metadata(id)
+            .map(|metadata| metadata.name())
+            .unwrap_or_default()
+            .to_owned();
+        if let Some(fields) = human_event
+            .spans
+            .last()
+            .map(|span| span.fields.get(human_event.color))
+            .filter(|fields| !fields.is_empty())
+        {
+            header.push_str(&format!("{{{}}}", fields.join(" ")));
+        }
+        human_event.fields.message = Some(header);
+        let style = human_event.style.to_mut();
+        style.initial_indent_text = "".into();
+        style.message = style.span_name;
+        human_event
+    }
+}
+
+impl<W, S> HumanLayer<W, S>
+where
+    W: OutputWriter,
+    S: ProvideStyle,
+{
+    /// Should progress bars be drawn below the other output?
+    fn live_progress(&self) -> bool {
+        self.progress && self.output_is_terminal
+    }
+
+    /// Should the status footer be drawn below the other output?
+    fn live_status(&self) -> bool {
+        self.status_footer && self.output_is_terminal
+    }
+
+    /// The width to draw progress bars and the status footer in.
+    fn live_width(&self) -> usize {
+        self.textwrap_options
+            .as_ref()
+            .map_or_else(textwrap::termwidth, TextWrapOptionsOwned::width)
+    }
+
+    /// Update the progress for a span with progress fields, drawing its progress bar or
+    /// writing its progress as a plain line.
+    fn update_progress<U>(&self, id: &Id, ctx: &Context<'_, U>)
+    where
+        U: tracing::Subscriber,
+        U: for<'lookup> LookupSpan<'lookup>,
+    {
+        let Some(span_ref) = ctx.span(id) else {
+            return;
+        };
+        let metadata = span_ref.metadata();
+        if !self.progress || !Progress::is_declared(metadata.fields()) {
+            return;
+        }
+        let Some(progress) = span_ref
+            .extensions()
+            .get::<SpanFieldsByLayer>()
+            .and_then(|by_layer| by_layer.get(self.id))
+            .map(|span_fields| Progress::from_fields(&span_fields.fields))
+        else {
+            return;
+        };
+        let style = self.styles.for_metadata(metadata);
+
+        let (print, result) = {
+            let mut output = self.output_writer.lock();
+            let output = &mut *output;
+            output
+                .progress
+                .update(id, metadata.name(), progress, &style);
+            if self.live_progress() {
+                (false, self.redraw_live(output))
+            } else {
+                let print = output.progress.should_print(id, self.progress_interval);
+                (print, Ok(()))
+            }
+        };
+        self.check_write(result);
+
+        if print {
+            let mut human_event = self.event_for_id(id, ctx);
+            // The span's name is in the message, so it doesn't need to be listed.
+            human_event.spans.pop();
+            human_event.fields.message = Some(format!("{} {progress}", metadata.name()));
+            self.write_event(metadata, human_event);
         }
     }
 
-    fn event_for_id<U>(&self, id: &Id, ctx: Context<'_, U>) -> HumanEvent<'_>
+    /// Add an entered span to the status footer.
+    fn enter_status<U>(&self, id: &Id, ctx: &Context<'_, U>)
     where
         U: tracing::Subscriber,
         U: for<'lookup> LookupSpan<'lookup>,
     {
-        self.event(
-            ctx.metadata(id)
-                .expect("Metadata should exist for the span ID"),
-            ctx.span_scope(id),
-        )
+        if !self.live_status() {
+            return;
+        }
+        let Some(span_ref) = ctx.span(id) else {
+            return;
+        };
+        let metadata = span_ref.metadata();
+        let fields = span_ref
+            .extensions()
+            .get::<SpanFieldsByLayer>()
+            .and_then(|by_layer| by_layer.get(self.id))
+            .map(|span_fields| span_fields.formatted.clone())
+            .unwrap_or_default();
+        let style = self.styles.for_metadata(metadata);
+
+        let result = {
+            let mut output = self.output_writer.lock();
+            if output.status.enter(id, metadata.name(), fields, &style) {
+                self.redraw_live(&mut output)
+            } else {
+                Ok(())
+            }
+        };
+        self.check_write(result);
+    }
+
+    /// Update a span's fields in the status footer.
+    fn record_status(&self, id: &Id, fields: FormattedFields) {
+        if !self.live_status() {
+            return;
+        }
+        let result = {
+            let mut output = self.output_writer.lock();
+            if output.status.record(id, fields) {
+                self.redraw_live(&mut output)
+            } else {
+                Ok(())
+            }
+        };
+        self.check_write(result);
+    }
+
+    /// Hide an exited span from the status footer, if it isn't entered on another thread.
+    fn exit_status(&self, id: &Id) {
+        if !self.live_status() {
+            return;
+        }
+        let result = {
+            let mut output = self.output_writer.lock();
+            if output.status.exit(id) {
+                self.redraw_live(&mut output)
+            } else {
+                Ok(())
+            }
+        };
+        self.check_write(result);
+    }
+
+    /// Remove a closed span's progress bar and status footer line, if it has them.
+    fn remove_live(&self, id: &Id) {
+        if !self.progress && !self.live_status() {
+            return;
+        }
+        let result = {
+            let mut output = self.output_writer.lock();
+            let removed = output.progress.remove(id) | output.status.remove(id);
+            if removed && (self.live_progress() || self.live_status()) {
+                self.redraw_live(&mut output)
+            } else {
+                Ok(())
+            }
+        };
+        self.check_write(result);
+    }
+
+    /// Erase and redraw the progress bars and status footer.
+    fn redraw_live(&self, output: &mut Output<W>) -> io::Result<()> {
+        let cleared = output.clear_live();
+        let drawn = output.draw_live(self.live_width(), self.color, self.status_footer_threshold);
+        cleared.and(drawn)
+    }
+
+    /// Format the event and write it to the `output_writer`, or the [`Destination`] it's
+    /// routed to, and then to each tee.
+    fn write_event<'a>(&'a self, metadata: &Metadata<'_>, mut human_event: HumanEvent<'a>) {
+        match self.route(metadata) {
+            Some(destination) => {
+                self.write_to(&destination.output, false, metadata, &mut human_event);
+            }
+            None => {
+                let live = self.live_progress() || self.live_status();
+                self.write_to(&self.output_writer, live, metadata, &mut human_event);
+            }
+        }
+
+        for tee in &self.tees {
+            if human_event.color == ShouldColor::Always && tee.color != ShouldColor::Always {
+                // Some messages are formatted ahead of time, like the headers for spans shown
+                // as a tree.
+                if let Some(message) = &mut human_event.fields.message {
+                    if let Cow::Owned(stripped) = strip_ansi(message) {
+                        *message = stripped;
+                    }
+                }
+            }
+            human_event.color = tee.color;
+            human_event.textwrap_options =
+                tee.textwrap_options.as_ref().map(|options| options.into());
+            self.write_to(&tee.output, false, metadata, &mut human_event);
+        }
+    }
+
+    /// Format the event and write it to the `output`, redrawing the progress bars and status
+    /// footer below it if `live` is set.
+    ///
+    /// Events are formatted into a reusable per-thread buffer before locking the `output`, so
+    /// that the lock is only held while writing. [`TimestampFormat::Delta`] timestamps are the
+    /// exception: they're measured while holding the lock, so they match the order the events
+    /// are written in, and the event is formatted after that.
+    ///
+    /// Blank lines around the event, and whether its spans are unchanged, are decided while
+    /// holding the lock, so that they always match the events written before it.
+    fn write_to<W2>(
+        &self,
+        output: &Mutex<Output<W2>>,
+        live: bool,
+        metadata: &Metadata<'_>,
+        human_event: &mut HumanEvent<'_>,
+    ) where
+        W2: OutputWriter,
+    {
+        thread_local! {
+            static BUFFER: RefCell<String> = const { RefCell::new(String::new()) };
+        }
+
+        /// Don't hold on to buffers used for unusually large events forever.
+        const MAX_RETAINED_CAPACITY: usize = 64 * 1024;
+
+        let delta = self
+            .timestamps
+            .as_ref()
+            .filter(|timestamps| timestamps.format() == TimestampFormat::Delta);
+        let mut write = |buffer: &mut String| {
+            let locked = delta.map(|timestamps| {
+                let mut output = output.lock();
+                human_event.timestamp = Some(timestamps.delta(&mut output.last_event));
+                output
+            });
+
+            // Format the event with a blank line on either side, and then write only the blank
+            // lines we need. The spans are written separately, so they can be left out if
+            // they're unchanged.
+            buffer.clear();
+            buffer.push('\n');
+            let _ = write!(buffer, "{}", human_event.body());
+            let spans_start = buffer.len();
+            let _ = write!(buffer, "{}", human_event.span_context());
+            let spans_end = buffer.len();
+            buffer.push('\n');
+            if self.unchanged_spans == UnchangedSpans::Mark && spans_start != spans_end {
+                buffer.push_str(&human_event.unchanged_spans_marker());
+            }
+
+            let root_span = human_event.spans.first().map(|span| &span.id);
+            let mut output = locked.unwrap_or_else(|| output.lock());
+            let output = &mut *output;
+            let cleared = if live { output.clear_live() } else { Ok(()) };
+            let spacing =
+                self.event_spacing
+                    .next(&mut output.spacing, human_event.is_long.get(), root_span);
+            let spans_unchanged = self.unchanged_spans != UnchangedSpans::Show
+                && output.last_spans.update(&human_event.spans)
+                && spans_start != spans_end;
+
+            // Cut the event down to the parts we need, so it can be written all at once.
+            let spans = match (spans_unchanged, self.unchanged_spans) {
+                (false, _) => spans_start..spans_end,
+                (true, UnchangedSpans::Mark) => spans_end + 1..buffer.len(),
+                (true, _) => spans_start..spans_start,
+            };
+            buffer.truncate(spans.end);
+            buffer.replace_range(spans_start..spans.start, "");
+            if spacing.after {
+                buffer.push('\n');
+            }
+            let start = if spacing.before { 0 } else { 1 };
+            let written = output
+                .writer
+                .write_output(Some(metadata), &buffer.as_bytes()[start..]);
+            let drawn = if live {
+                output.draw_live(self.live_width(), self.color, self.status_footer_threshold)
+            } else {
+                Ok(())
+            };
+            cleared.and(written).and(drawn)
+        };
+
+        let result = BUFFER.with(|buffer| match buffer.try_borrow_mut() {
+            Ok(mut buffer) => {
+                let result = write(&mut buffer);
+                if buffer.capacity() > MAX_RETAINED_CAPACITY {
+                    *buffer = String::new();
+                }
+                result
+            }
+            // If writing the event emits another event (for example, from a custom writer), the
+            // buffer is already in use.
+            Err(_) => write(&mut String::new()),
+        });
+        // This is done after the `output` is unlocked, in case a callback logs an event.
+        self.check_write(result);
+    }
+
+    /// Handle the result of writing output, according to the `write_error_policy`.
+    fn check_write(&self, result: io::Result<()>) {
+        let Err(error) = result else {
+            return;
+        };
+        match &self.write_error_policy {
+            WriteErrorPolicy::Ignore => {}
+            WriteErrorPolicy::Callback(callback) => {
+                thread_local! {
+                    /// Set while a write error callback is running on this thread.
+                    static IN_CALLBACK: Cell<bool> = const { Cell::new(false) };
+                }
+
+                /// Unsets `IN_CALLBACK` when the callback returns, even if it panics.
+                struct Reset;
+
+                impl Drop for Reset {
+                    fn drop(&mut self) {
+                        IN_CALLBACK.with(|in_callback| in_callback.set(false));
+                    }
+                }
+
+                // If the callback logs an event and writing that fails too, calling the
+                // callback again would recurse forever.
+                if !IN_CALLBACK.with(|in_callback| in_callback.replace(true)) {
+                    let _reset = Reset;
+                    callback(&error);
+                }
+            }
+            WriteErrorPolicy::Count(count) => count.increment(),
+            WriteErrorPolicy::DisableOnBrokenPipe => {
+                if error.kind() == io::ErrorKind::BrokenPipe {
+                    self.disabled.store(true, Ordering::Relaxed);
+                }
+            }
+        }
     }
 }
 
@@ -194,97 +1023,1078 @@ where
     Sub: Subscriber,
     Sub: for<'lookup> LookupSpan<'lookup>,
     Self: 'static,
-    Wr: Write,
+    Wr: OutputWriter,
     Sty: ProvideStyle,
 {
     fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &Id, ctx: Context<'_, Sub>) {
-        let mut fields = HumanFields::new_span();
+        if self.is_disabled() {
+            return;
+        }
+        let mut fields = HumanFields::new_span(self.quote_strings);
         attrs.record(&mut fields);
         if let Some(span_ref) = ctx.span(id) {
-            span_ref
-                .extensions_mut()
-                .insert(FormattedFields::<HumanLayer>::new(
-                    StyledSpanFields {
-                        style: self.styles.for_metadata(attrs.metadata()),
-                        color: self.color_output,
-                        fields,
-                    }
-                    .to_string(),
-                ));
+            let fields = SpanFields::new(
+                fields,
+                &self.styles.for_metadata(attrs.metadata()),
+                self.colors(),
+            );
+            let mut extensions = span_ref.extensions_mut();
+            SpanFieldsByLayer::insert_into(&mut extensions, self.id, fields);
+
+            // Other `HumanLayer`s may have already started tracking the span's timings.
+            if self.span_events.clone() & FmtSpan::CLOSE != FmtSpan::NONE
+                && extensions.get_mut::<SpanTimings>().is_none()
+            {
+                extensions.insert(SpanTimings::new());
+            }
+            drop(extensions);
 
             if self.span_events.clone() & FmtSpan::NEW != FmtSpan::NONE {
-                let mut human_event = self.event(span_ref.metadata(), ctx.span_scope(id));
+                let mut human_event = self.event_for_id(id, &ctx);
                 human_event.fields.message = Some("new".into());
-                let _ = write!(self.output_writer.lock(), "{human_event}");
-                self.update_long(human_event.last_event_was_long);
+                self.write_event(attrs.metadata(), human_event);
             }
+            self.update_progress(id, &ctx);
         }
     }
 
     fn on_record(&self, id: &Id, values: &span::Record<'_>, ctx: Context<'_, Sub>) {
-        let mut fields = HumanFields::new_span();
-        values.record(&mut fields);
+        if self.is_disabled() {
+            return;
+        }
+        let mut recorded = HumanFields::new_span(self.quote_strings);
+        values.record(&mut recorded);
         if let Some(span_ref) = ctx.span(id) {
-            span_ref
-                .extensions_mut()
-                .insert(FormattedFields::<HumanLayer>::new(
-                    StyledSpanFields {
-                        style: self.styles.for_metadata(span_ref.metadata()),
-                        color: self.color_output,
-                        fields,
-                    }
-                    .to_string(),
-                ));
+            let metadata = span_ref.metadata();
+            // Only keep a copy of the new values if we're going to print them.
+            let changed = self.span_record_events.then(|| recorded.clone());
+
+            let style = self.styles.for_metadata(metadata);
+            let mut extensions = span_ref.extensions_mut();
+            let formatted = match extensions
+                .get_mut::<SpanFieldsByLayer>()
+                .and_then(|by_layer| by_layer.get_mut(self.id))
+            {
+                Some(span_fields) => {
+                    span_fields.record(recorded, metadata.fields(), &style, self.colors());
+                    span_fields.formatted.clone()
+                }
+                None => {
+                    let fields = SpanFields::new(recorded, &style, self.colors());
+                    let formatted = fields.formatted.clone();
+                    SpanFieldsByLayer::insert_into(&mut extensions, self.id, fields);
+                    formatted
+                }
+            };
+            drop(extensions);
+            self.record_status(id, formatted);
+
+            if let Some(changed) = changed {
+                let mut human_event = self.event(metadata, ctx.span_scope(id));
+                human_event.fields.message = Some(format!("{} updated", metadata.name()));
+                human_event.fields.fields = changed.fields;
+                self.write_event(metadata, human_event);
+            }
         }
+        self.update_progress(id, &ctx);
     }
 
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, Sub>) {
+        if self.is_disabled() {
+            return;
+        }
         let mut human_event = self.event(event.metadata(), ctx.event_scope(event));
         event.record(&mut human_event);
-        let _ = write!(self.output_writer.lock(), "{human_event}");
-        self.update_long(human_event.last_event_was_long);
+        self.write_event(event.metadata(), human_event);
     }
 
     fn on_enter(&self, id: &Id, ctx: Context<'_, Sub>) {
+        if self.is_disabled() {
+            return;
+        }
+        if let Some(span_ref) = ctx.span(id) {
+            let mut extensions = span_ref.extensions_mut();
+            if let Some(timings) = extensions.get_mut::<SpanTimings>() {
+                timings.enter();
+            }
+            let first_enter = extensions
+                .get_mut::<SpanFieldsByLayer>()
+                .and_then(|by_layer| by_layer.get_mut(self.id))
+                .is_some_and(|fields| !std::mem::replace(&mut fields.entered, true));
+            drop(extensions);
+
+            if self.span_tree && first_enter {
+                self.write_event(span_ref.metadata(), self.tree_header(id, &ctx));
+            }
+        }
+        self.enter_status(id, &ctx);
+
         if self.span_events.clone() & FmtSpan::ENTER != FmtSpan::NONE {
-            let mut human_event = self.event_for_id(id, ctx);
+            let mut human_event = self.event_for_id(id, &ctx);
             human_event.fields.message = Some("enter".into());
-            let _ = write!(self.output_writer.lock(), "{human_event}");
-            self.update_long(human_event.last_event_was_long);
+            self.write_event(Self::span_metadata(id, &ctx), human_event);
         }
     }
 
     fn on_exit(&self, id: &Id, ctx: Context<'_, Sub>) {
+        if self.is_disabled() {
+            return;
+        }
+        if let Some(span_ref) = ctx.span(id) {
+            if let Some(timings) = span_ref.extensions_mut().get_mut::<SpanTimings>() {
+                timings.exit();
+            }
+        }
+        self.exit_status(id);
+
         if self.span_events.clone() & FmtSpan::EXIT != FmtSpan::NONE {
-            let mut human_event = self.event_for_id(id, ctx);
+            let mut human_event = self.event_for_id(id, &ctx);
             human_event.fields.message = Some("exit".into());
-            let _ = write!(self.output_writer.lock(), "{human_event}");
-            self.update_long(human_event.last_event_was_long);
+            self.write_event(Self::span_metadata(id, &ctx), human_event);
         }
     }
 
     fn on_close(&self, id: Id, ctx: Context<'_, Sub>) {
+        if self.is_disabled() {
+            return;
+        }
+        self.remove_live(&id);
         if self.span_events.clone() & FmtSpan::CLOSE != FmtSpan::NONE {
-            let mut human_event = self.event_for_id(&id, ctx);
-            human_event.fields.message = Some("close".into());
-            let _ = write!(self.output_writer.lock(), "{human_event}");
-            self.update_long(human_event.last_event_was_long);
+            let Some(span_ref) = ctx.span(&id) else {
+                return;
+            };
+            let metadata = span_ref.metadata();
+            let timing = span_ref
+                .extensions()
+                .get::<SpanTimings>()
+                .map(SpanTimings::close);
+
+            let mut human_event = self.event_for_id(&id, &ctx);
+            if let Some(timing) = timing {
+                human_event.style = self.styles.for_span_close(metadata, timing);
+            }
+            // Close events are marked with a different first-line indent, like `✓ `.
+            let style = human_event.style.to_mut();
+            style.initial_indent_text = style.span_close_text.clone();
+            human_event.fields.message = Some(match timing {
+                Some(timing) => self.span_timing_thresholds.message(metadata.name(), timing),
+                None => format!("{} finished", metadata.name()),
+            });
+            self.write_event(metadata, human_event);
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io::Write;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
     use expect_test::expect;
+    use tracing::Level;
+    use tracing_subscriber::filter::filter_fn;
+    use tracing_subscriber::filter::LevelFilter;
+    use tracing_subscriber::fmt::writer::MakeWriterExt;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::reload;
 
     use super::*;
+    use crate::NonBlocking;
+    use crate::WriteErrorCount;
+
+    /// A writer which can be read from after the [`HumanLayer`] is installed.
+    #[derive(Clone, Default)]
+    struct TestWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for TestWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl MaybeTerminal for TestWriter {}
+
+    /// Configure the `layer` to write to a [`TestWriter`] without colors.
+    fn test_layer(layer: HumanLayer) -> (HumanLayer<TestWriter>, TestWriter) {
+        let writer = TestWriter::default();
+        let layer = layer
+            .with_color_output(false)
+            .with_output_writer(writer.clone());
+        (layer, writer)
+    }
+
+    impl TestWriter {
+        fn output(&self) -> String {
+            String::from_utf8(self.0.lock().clone()).unwrap()
+        }
+    }
+
+    /// Run `f` with the given layer installed and return its output.
+    fn capture(layer: HumanLayer, f: impl FnOnce()) -> String {
+        let (layer, writer) = test_layer(layer);
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), f);
+        writer.output()
+    }
+
+    #[test]
+    fn test_record_merges_fields() {
+        expect![[r#"
+            • Copying.
+              in copy{status=200 path=old.txt}
+            • Copying.
+              in copy{status=200 path=new.txt}
+        "#]]
+        .assert_eq(&capture(HumanLayer::new(), || {
+            // Fields recorded later show up where they're declared.
+            let span =
+                tracing::info_span!("copy", status = tracing::field::Empty, path = "old.txt",);
+            let _guard = span.enter();
+            span.record("status", 200);
+            tracing::info!("Copying.");
+            span.record("path", "new.txt");
+            tracing::info!("Copying.");
+        }));
+    }
+
+    #[test]
+    fn test_several_layers() {
+        let colored = TestWriter::default();
+        let plain = TestWriter::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(
+                HumanLayer::new()
+                    .with_color_output(true)
+                    .with_output_writer(colored.clone()),
+            )
+            .with(
+                HumanLayer::new()
+                    .with_color_output(false)
+                    .with_output_writer(plain.clone()),
+            );
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("copy", path = "old.txt");
+            let _guard = span.enter();
+            tracing::info!("Copying.");
+        });
+
+        expect![[r#"
+            [32m• [0mCopying.
+              [2min [0mcopy{[1mpath[0m=old.txt}
+        "#]]
+        .assert_eq(&colored.output());
+        expect![[r#"
+            • Copying.
+              in copy{path=old.txt}
+        "#]]
+        .assert_eq(&plain.output());
+    }
+
+    #[test]
+    fn test_reload_after_span_created() {
+        // The new layer is added after the span is created, so it never sees `on_new_span`.
+        let (old_layer, _) = test_layer(HumanLayer::new());
+        let (layer, writer) = test_layer(HumanLayer::new().with_span_events(FmtSpan::FULL));
+        let (reload_layer, handle) = reload::Layer::new(old_layer);
+        let subscriber = tracing_subscriber::registry().with(reload_layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let span =
+                tracing::info_span!("copy", path = "old.txt", status = tracing::field::Empty);
+            let _guard = span.enter();
+            handle.reload(layer).unwrap();
+            tracing::info!("Copying.");
+            span.record("status", 200);
+            tracing::info!("Copying.");
+        });
+
+        expect![[r#"
+            • Copying.
+              in copy
+            • Copying.
+              in copy{status=200}
+            • exit
+              in copy{status=200}
+            ✓ copy finished
+              in copy{status=200}
+        "#]]
+        .assert_eq(&writer.output());
+    }
+
+    #[test]
+    fn test_filtered() {
+        let (layer, writer) = test_layer(HumanLayer::new().with_span_events(FmtSpan::FULL));
+        let subscriber = tracing_subscriber::registry().with(layer.with_filter(LevelFilter::INFO));
+        tracing::subscriber::with_default(subscriber, || {
+            let outer = tracing::info_span!("copy", path = "old.txt");
+            let _outer = outer.enter();
+            let inner = tracing::debug_span!("inner", path = "old.txt");
+            let _inner = inner.enter();
+            tracing::info!("Copying.");
+            tracing::debug!("Hidden.");
+        });
+
+        expect![[r#"
+            • new
+              in copy{path=old.txt}
+            • enter
+              in copy{path=old.txt}
+            • Copying.
+              in copy{path=old.txt}
+            • exit
+              in copy{path=old.txt}
+            ✓ copy finished
+              in copy{path=old.txt}
+        "#]]
+        .assert_eq(&writer.output());
+    }
+
+    #[test]
+    fn test_filtered_by_span() {
+        // This filter enables the event but not its span, so the layer never sees `on_new_span`.
+        let (layer, writer) = test_layer(HumanLayer::new());
+        let subscriber = tracing_subscriber::registry()
+            .with(layer.with_filter(filter_fn(|metadata| !metadata.is_span())));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("copy", path = "old.txt");
+            let _guard = span.enter();
+            tracing::info!("Copying.");
+        });
+
+        expect![[r#"
+            • Copying.
+        "#]]
+        .assert_eq(&writer.output());
+    }
+
+    #[test]
+    fn test_record_events() {
+        expect![[r#"
+            • copy updated status=200
+              in copy{path=old.txt status=200}
+        "#]]
+        .assert_eq(&capture(
+            HumanLayer::new().with_span_record_events(true),
+            || {
+                let span =
+                    tracing::info_span!("copy", path = "old.txt", status = tracing::field::Empty);
+                span.record("status", 200);
+            },
+        ));
+    }
+
+    #[test]
+    fn test_event_spacing_between_root_spans() {
+        expect![[r#"
+            • Copying.
+              in copy
+            • Copying.
+              in file
+              in copy
+
+            • Deleting.
+              in delete
+        "#]]
+        .assert_eq(&capture(
+            HumanLayer::new().with_event_spacing(EventSpacing::BetweenRootSpans),
+            || {
+                tracing::info_span!("copy").in_scope(|| {
+                    tracing::info!("Copying.");
+                    tracing::info_span!("file").in_scope(|| tracing::info!("Copying."));
+                });
+                tracing::info_span!("delete").in_scope(|| tracing::info!("Deleting."));
+            },
+        ));
+    }
+
+    #[test]
+    fn test_event_spacing_concurrent() {
+        let (layer, writer) = test_layer(HumanLayer::new());
+        let dispatch = tracing::Dispatch::new(tracing_subscriber::registry().with(layer));
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    tracing::dispatcher::with_default(&dispatch, || {
+                        for _ in 0..100 {
+                            tracing::info!("Long event.\nSecond line.");
+                        }
+                    })
+                });
+            }
+        });
+
+        // Exactly one blank line between each long event.
+        assert_eq!(
+            writer.output(),
+            format!("\n{}", "• Long event.\n  Second line.\n\n".repeat(400))
+        );
+    }
+
+    fn emit_in_nested_spans() {
+        let _outer = tracing::info_span!("outer", a = 1).entered();
+        let _copy = tracing::info_span!("copy", path = "a.txt").entered();
+        let _inner = tracing::info_span!("inner", n = 2).entered();
+        tracing::info!("Copying.");
+    }
+
+    #[test]
+    fn test_span_display() {
+        let output = [
+            SpanDisplay::Full,
+            SpanDisplay::RootFirst,
+            SpanDisplay::Leaf,
+            SpanDisplay::Breadcrumb,
+            SpanDisplay::MaxDepth(2),
+            SpanDisplay::None,
+        ]
+        .into_iter()
+        .map(|span_display| {
+            format!(
+                "{span_display:?}:\n{}",
+                capture(
+                    HumanLayer::new().with_span_display(span_display),
+                    emit_in_nested_spans
+                )
+            )
+        })
+        .collect::<String>();
+
+        expect![[r#"
+            Full:
+            • Copying.
+              in inner{n=2}
+              in copy{path=a.txt}
+              in outer{a=1}
+            RootFirst:
+            • Copying.
+              in outer{a=1}
+              in copy{path=a.txt}
+              in inner{n=2}
+            Leaf:
+            • Copying.
+              in inner{n=2}
+            Breadcrumb:
+            • Copying.
+              in outer › copy › inner{n=2}
+            MaxDepth(2):
+            • Copying.
+              in inner{n=2}
+              in copy{path=a.txt}
+            None:
+            • Copying.
+        "#]]
+        .assert_eq(&output);
+    }
+
+    #[test]
+    fn test_span_display_none_between_root_spans() {
+        expect![[r#"
+            • Copying.
+            • Copying.
+
+            • Deleting.
+        "#]]
+        .assert_eq(&capture(
+            HumanLayer::new()
+                .with_span_display(SpanDisplay::None)
+                .with_event_spacing(EventSpacing::BetweenRootSpans),
+            || {
+                tracing::info_span!("copy").in_scope(|| {
+                    tracing::info!("Copying.");
+                    tracing::info_span!("file").in_scope(|| tracing::info!("Copying."));
+                });
+                tracing::info_span!("delete").in_scope(|| tracing::info!("Deleting."));
+            },
+        ));
+    }
+
+    #[test]
+    fn test_span_display_per_level() {
+        let styles = LayerStyles::new().with_span_display(SpanDisplay::Leaf);
+        let styles = LayerStyles {
+            warn: styles.warn.with_span_display(SpanDisplay::Full),
+            ..styles
+        };
+        let (layer, writer) = test_layer(HumanLayer::new());
+        let layer = layer.with_style_provider(styles);
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let _outer = tracing::info_span!("outer").entered();
+            let _inner = tracing::info_span!("inner").entered();
+            tracing::info!("Copying.");
+            tracing::warn!("Disk almost full.");
+        });
+        expect![[r#"
+            • Copying.
+              in inner
+            ⚠ Disk almost full.
+              in inner
+              in outer
+        "#]]
+        .assert_eq(&writer.output());
+    }
+
+    fn emit_in_changing_spans() {
+        let copy = tracing::info_span!("copy", path = "a.txt");
+        copy.in_scope(|| {
+            tracing::info!("Copying.");
+            tracing::info!("Still copying.");
+            // Recording a field changes the span context.
+            copy.record("path", "b.txt");
+            tracing::info!("Copying another file.");
+            tracing::info_span!("verify").in_scope(|| tracing::info!("Verifying."));
+            tracing::info!("Done copying.");
+        });
+        tracing::info!("Done.");
+    }
+
+    #[test]
+    fn test_unchanged_spans() {
+        let output = [UnchangedSpans::Omit, UnchangedSpans::Mark]
+            .into_iter()
+            .map(|unchanged_spans| {
+                format!(
+                    "{unchanged_spans:?}:\n{}",
+                    capture(
+                        HumanLayer::new().with_unchanged_spans(unchanged_spans),
+                        emit_in_changing_spans
+                    )
+                )
+            })
+            .collect::<String>();
+
+        expect![[r#"
+            Omit:
+            • Copying.
+              in copy{path=a.txt}
+            • Still copying.
+            • Copying another file.
+              in copy{path=b.txt}
+            • Verifying.
+              in verify
+              in copy{path=b.txt}
+            • Done copying.
+              in copy{path=b.txt}
+            • Done.
+            Mark:
+            • Copying.
+              in copy{path=a.txt}
+            • Still copying.
+              〃
+            • Copying another file.
+              in copy{path=b.txt}
+            • Verifying.
+              in verify
+              in copy{path=b.txt}
+            • Done copying.
+              in copy{path=b.txt}
+            • Done.
+        "#]]
+        .assert_eq(&output);
+    }
+
+    #[test]
+    fn test_span_tree() {
+        expect![[r#"
+            copy{path=a.txt}
+            │ • Copying.
+            │ verify
+            │ │ ⚠ Checksum mismatch. checksum=abc123
+            │ ✓ verify finished
+            │ • Copied.
+            ✓ copy finished
+            • Done.
+        "#]]
+        .assert_eq(&capture(
+            HumanLayer::new()
+                .with_span_tree(true)
+                .with_span_events(FmtSpan::CLOSE),
+            || {
+                tracing::info_span!("copy", path = "a.txt").in_scope(|| {
+                    tracing::info!("Copying.");
+                    tracing::info_span!("verify").in_scope(|| {
+                        tracing::warn!(checksum = "abc123", "Checksum mismatch.");
+                    });
+                    tracing::info!("Copied.");
+                });
+                tracing::info!("Done.");
+            },
+        ));
+    }
+
+    #[test]
+    fn test_span_tree_wrapping() {
+        // The guides are subtracted from the wrap width.
+        expect![[r#"
+            outer
+            │ inner
+
+            │ │ • Copying a great many
+            │ │   photos to the backup
+            │ │   drive.
+            │ │   path=/home/puppy/photos
+
+        "#]]
+        .assert_eq(&capture(
+            HumanLayer::new()
+                .with_span_tree(true)
+                .with_textwrap_options(Some(TextWrapOptionsOwned::new().with_width(30))),
+            || {
+                let _outer = tracing::info_span!("outer").entered();
+                let _inner = tracing::info_span!("inner").entered();
+                tracing::info!(
+                    path = "/home/puppy/photos",
+                    "Copying a great many photos to the backup drive."
+                );
+            },
+        ));
+    }
+
+    #[test]
+    fn test_progress_plain() {
+        expect![[r#"
+            • download 0/300 (0%)
+            • download 100/300 (33%)
+            • Connection reset, retrying.
+              in download{progress.current=100 progress.total=300}
+            • download 300/300 (100%)
+        "#]]
+        .assert_eq(&capture(
+            HumanLayer::new()
+                .with_progress(true)
+                .with_progress_interval(Duration::ZERO),
+            || {
+                let span =
+                    tracing::info_span!("download", progress.current = 0, progress.total = 300);
+                span.record("progress.current", 100);
+                span.in_scope(|| tracing::info!("Connection reset, retrying."));
+                span.record("progress.current", 300);
+            },
+        ));
+    }
+
+    /// A terminal emulator, so that we can check what progress bars look like on screen.
+    #[derive(Clone)]
+    struct TestTerminal(Arc<Mutex<vt100::Parser>>);
+
+    impl TestTerminal {
+        fn new() -> Self {
+            Self(Arc::new(Mutex::new(vt100::Parser::new(10, 60, 0))))
+        }
+
+        fn contents(&self) -> String {
+            self.0.lock().screen().contents()
+        }
+    }
+
+    impl Write for TestTerminal {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            // Terminals usually translate newlines into carriage returns and newlines.
+            let buf_crlf = String::from_utf8_lossy(buf).replace('\n', "\r\n");
+            self.0.lock().process(buf_crlf.as_bytes());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl MaybeTerminal for TestTerminal {
+        fn is_terminal(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_progress_terminal() {
+        let terminal = TestTerminal::new();
+        let layer = HumanLayer::new()
+            .with_color_output(false)
+            .with_progress(true)
+            .with_textwrap_options(Some(TextWrapOptionsOwned::new().with_width(60)))
+            .with_output_writer_detect(terminal.clone());
+        let mut screens = Vec::new();
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let download =
+                tracing::info_span!("download", progress.current = 0, progress.total = 200);
+            let unpack = tracing::info_span!("unpack", progress.current = tracing::field::Empty);
+            download.record("progress.current", 50);
+            screens.push(terminal.contents());
+
+            tracing::info!("Connection reset, retrying.");
+            download.record("progress.current", 150);
+            unpack.record("progress.current", 3);
+            screens.push(terminal.contents());
+
+            drop(download);
+            tracing::info!("Downloaded.");
+            screens.push(terminal.contents());
+        });
+
+        expect![[r#"
+            download ███████░░░░░░░░░░░░░░░░░░░░░░░ 50/200  25%
+            ⠋ unpack 0
+            ---
+            • Connection reset, retrying.
+            download ██████████████████████░░░░░░░░ 150/200  75%
+            ⠙ unpack 3
+            ---
+            • Connection reset, retrying.
+            • Downloaded.
+            ⠙ unpack 3"#]]
+        .assert_eq(&screens.join("\n---\n"));
+    }
+
+    #[test]
+    fn test_status_footer() {
+        let terminal = TestTerminal::new();
+        let layer = HumanLayer::new()
+            .with_color_output(false)
+            .with_status_footer(true)
+            .with_status_footer_threshold(Duration::ZERO)
+            .with_textwrap_options(Some(TextWrapOptionsOwned::new().with_width(60)))
+            .with_output_writer_terminal(terminal.clone(), true);
+        let mut screens = Vec::new();
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let build = tracing::info_span!("build", krate = "foo", jobs = 4).entered();
+            let compile = tracing::info_span!("compile", file = "lib.rs").entered();
+            screens.push(terminal.contents());
+
+            tracing::info!("Compiling.");
+            build.record("jobs", 8);
+            screens.push(terminal.contents());
+
+            drop(compile);
+            tracing::info!("Compiled.");
+            screens.push(terminal.contents());
+
+            drop(build);
+            screens.push(terminal.contents());
+        });
+
+        expect![[r#"
+            build{krate=foo jobs=4} 0s
+            compile{file=lib.rs} 0s
+            ---
+            • Compiling.
+              in compile{file=lib.rs}
+              in build{krate=foo jobs=4}
+            build{krate=foo jobs=8} 0s
+            compile{file=lib.rs} 0s
+            ---
+            • Compiling.
+              in compile{file=lib.rs}
+              in build{krate=foo jobs=4}
+            • Compiled.
+              in build{krate=foo jobs=8}
+            build{krate=foo jobs=8} 0s
+            ---
+            • Compiling.
+              in compile{file=lib.rs}
+              in build{krate=foo jobs=4}
+            • Compiled.
+              in build{krate=foo jobs=8}"#]]
+        .assert_eq(&screens.join("\n---\n"));
+    }
+
+    #[test]
+    fn test_status_footer_quiet_span() {
+        let terminal = TestTerminal::new();
+        let layer = HumanLayer::new()
+            .with_color_output(false)
+            .with_status_footer(true)
+            .with_textwrap_options(Some(TextWrapOptionsOwned::new().with_width(60)))
+            .with_output_writer_detect(terminal.clone());
+        let screen =
+            tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+                // Nothing is written while the span runs, but it's still shown.
+                let _build = tracing::info_span!("build", krate = "foo").entered();
+                terminal.contents()
+            });
+
+        expect!["build{krate=foo} 0s"].assert_eq(&screen);
+    }
+
+    #[test]
+    fn test_status_footer_not_terminal() {
+        expect![[r#"
+            • Compiling.
+              in build
+        "#]]
+        .assert_eq(&capture(
+            HumanLayer::new()
+                .with_status_footer(true)
+                .with_status_footer_threshold(Duration::ZERO),
+            || {
+                let _build = tracing::info_span!("build").entered();
+                tracing::info!("Compiling.");
+            },
+        ));
+    }
+
+    #[test]
+    fn test_route() {
+        let stdout = TestWriter::default();
+        let narrow = TestWriter::default();
+        let (layer, stderr) = test_layer(
+            HumanLayer::new()
+                .with_span_events(FmtSpan::CLOSE)
+                .with_event_spacing(EventSpacing::Always)
+                .with_route(
+                    |metadata| metadata.target() == "narrow",
+                    Destination::new_terminal(narrow.clone(), false)
+                        .with_textwrap_options(Some(TextWrapOptionsOwned::new().with_width(30))),
+                )
+                .with_route(
+                    |metadata| *metadata.level() > Level::WARN,
+                    Destination::new(stdout.clone()),
+                ),
+        );
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let _span = tracing::info_span!("copy", path = "a.txt").entered();
+            tracing::info!("Copying.");
+            tracing::warn!("Disk almost full.");
+            tracing::info!(target: "narrow", "This event is written to a narrow destination.");
+            tracing::info!("Copied.");
+        });
+
+        // Routes decide their own colors and spacing.
+        expect![[r#"
+            [32m• [0mCopying.
+              [2min [0mcopy{[1mpath[0m=a.txt}
+
+            [32m• [0mCopied.
+              [2min [0mcopy{[1mpath[0m=a.txt}
+
+            [32m✓ [0mcopy finished
+              [2min [0mcopy{[1mpath[0m=a.txt}
+        "#]]
+        .assert_eq(&stdout.output());
+        expect![[r#"
+            ⚠ Disk almost full.
+              in copy{path=a.txt}
+        "#]]
+        .assert_eq(&stderr.output());
+        expect![[r#"
+            [32m• [0mThis event is written to a
+              narrow destination.
+              [2min [0mcopy{[1mpath[0m=a.txt}
+        "#]]
+        .assert_eq(&narrow.output());
+    }
+
+    #[test]
+    fn test_tee() {
+        let terminal = TestWriter::default();
+        let log = TestWriter::default();
+        let layer = HumanLayer::new()
+            .with_color_output(true)
+            .with_span_tree(true)
+            .with_textwrap_options(Some(TextWrapOptionsOwned::new().with_width(30)))
+            .with_output_writer(terminal.clone())
+            .with_tee(
+                Destination::new(log.clone())
+                    .with_color_output(false)
+                    .with_textwrap_options(None),
+            );
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let _span = tracing::info_span!("copy", path = "a.txt").entered();
+            tracing::info!(bytes = 1024, "Copying a file to somewhere else.");
+        });
+
+        expect![[r#"
+            copy{[1mpath[0m=a.txt}
+
+            [2m│ [0m[32m• [0mCopying a file to
+            [2m│ [0m  somewhere else.
+            [2m│ [0m  [1mbytes[0m=1024
+
+        "#]]
+        .assert_eq(&terminal.output());
+        expect![[r#"
+            copy{path=a.txt}
+            │ • Copying a file to somewhere else. bytes=1024
+        "#]]
+        .assert_eq(&log.output());
+    }
+
+    #[test]
+    fn test_make_writer() {
+        let warnings = TestWriter::default();
+        let info = TestWriter::default();
+        let make_writer = {
+            let warnings = warnings.clone();
+            let info = info.clone();
+            (move || warnings.clone())
+                .with_max_level(Level::WARN)
+                .or_else(move || info.clone())
+        };
+        let layer = HumanLayer::new()
+            .with_color_output(false)
+            .with_make_writer(make_writer);
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            tracing::info!("Copying.");
+            tracing::warn!("Disk almost full.");
+            tracing::info!("Copied.");
+        });
+
+        expect![[r#"
+            • Copying.
+            • Copied.
+        "#]]
+        .assert_eq(&info.output());
+        expect![[r#"
+            ⚠ Disk almost full.
+        "#]]
+        .assert_eq(&warnings.output());
+    }
+
+    #[test]
+    fn test_non_blocking() {
+        let output = TestWriter::default();
+        let (writer, guard) = NonBlocking::new(output.clone());
+        let layer = HumanLayer::new()
+            .with_color_output(false)
+            .with_output_writer(writer);
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let _span = tracing::info_span!("copy", path = "a.txt").entered();
+            tracing::info!("Copying.");
+            tracing::info!("Copied.");
+        });
+        guard.shutdown();
+
+        expect![[r#"
+            • Copying.
+              in copy{path=a.txt}
+            • Copied.
+              in copy{path=a.txt}
+        "#]]
+        .assert_eq(&output.output());
+    }
+
+    /// A writer which always fails, counting how many writes it's asked for.
+    #[derive(Clone)]
+    struct FailingWriter {
+        kind: io::ErrorKind,
+        attempts: Arc<AtomicUsize>,
+    }
+
+    impl FailingWriter {
+        fn new(kind: io::ErrorKind) -> Self {
+            Self {
+                kind,
+                attempts: Default::default(),
+            }
+        }
+
+        fn attempts(&self) -> usize {
+            self.attempts.load(Ordering::Relaxed)
+        }
+    }
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            self.attempts.fetch_add(1, Ordering::Relaxed);
+            Err(io::Error::from(self.kind))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl MaybeTerminal for FailingWriter {}
+
+    fn log_to_failing(policy: WriteErrorPolicy, writer: FailingWriter) {
+        let layer = HumanLayer::new()
+            .with_color_output(false)
+            .with_write_error_policy(policy)
+            .with_output_writer(writer);
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let _span = tracing::info_span!("copy", path = "a.txt").entered();
+            tracing::info!("Copying.");
+            tracing::warn!("Disk almost full.");
+            tracing::info!("Copied.");
+        });
+    }
+
+    #[test]
+    fn test_write_error_ignore() {
+        let writer = FailingWriter::new(io::ErrorKind::BrokenPipe);
+        log_to_failing(WriteErrorPolicy::Ignore, writer.clone());
+        assert_eq!(writer.attempts(), 3);
+    }
+
+    #[test]
+    fn test_write_error_callback() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let policy = {
+            let errors = errors.clone();
+            WriteErrorPolicy::callback(move |error| errors.lock().push(error.kind()))
+        };
+        log_to_failing(policy, FailingWriter::new(io::ErrorKind::StorageFull));
+        assert_eq!(*errors.lock(), [io::ErrorKind::StorageFull; 3]);
+    }
+
+    #[test]
+    fn test_write_error_count() {
+        let count = WriteErrorCount::new();
+        log_to_failing(
+            WriteErrorPolicy::Count(count.clone()),
+            FailingWriter::new(io::ErrorKind::StorageFull),
+        );
+        assert_eq!(count.get(), 3);
+    }
+
+    #[test]
+    fn test_write_error_disable_on_broken_pipe() {
+        let writer = FailingWriter::new(io::ErrorKind::BrokenPipe);
+        log_to_failing(WriteErrorPolicy::DisableOnBrokenPipe, writer.clone());
+        assert_eq!(writer.attempts(), 1);
+
+        // Other errors don't disable the layer.
+        let writer = FailingWriter::new(io::ErrorKind::StorageFull);
+        log_to_failing(WriteErrorPolicy::DisableOnBrokenPipe, writer.clone());
+        assert_eq!(writer.attempts(), 3);
+    }
+
+    #[test]
+    fn test_write_error_non_blocking() {
+        let failing = FailingWriter::new(io::ErrorKind::BrokenPipe);
+        let (writer, guard) = NonBlocking::new(failing.clone());
+        let count = WriteErrorCount::new();
+        let layer = HumanLayer::new()
+            .with_color_output(false)
+            .with_write_error_policy(WriteErrorPolicy::Count(count.clone()))
+            .with_output_writer(writer);
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            // The first write fails on the background thread, so it's reported by a later one.
+            while count.get() == 0 {
+                tracing::info!("Copying.");
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        });
+        guard.shutdown();
+
+        // Nothing is written after the first error.
+        assert_eq!(failing.attempts(), 1);
+    }
 
     #[test]
     fn test_debug() {
         expect![[r#"
             HumanLayer {
                 span_events: FmtSpan::NONE,
+                span_timing_thresholds: SpanTimingThresholds {
+                    elapsed: 100ms,
+                    idle: 100ms,
+                },
+                span_record_events: false,
                 color_output: Always,
+                color: Always,
                 textwrap_options: Some(
                     TextWrapOptionsOwned {
                         width: Fixed(
@@ -305,6 +2115,20 @@ mod tests {
                         word_splitter: NoHyphenation,
                     },
                 ),
+                quote_strings: WhenNeeded,
+                field_layout: Inline,
+                span_field_layout: Wrap,
+                unchanged_spans: Show,
+                span_tree: false,
+                progress: false,
+                progress_interval: 1s,
+                status_footer: false,
+                status_footer_threshold: 0ns,
+                routes: [],
+                tees: [],
+                write_error_policy: Ignore,
+                event_spacing: AroundLongEvents,
+                timestamps: None,
                 output_writer: "std::io::buffered::linewriter::LineWriter<std::io::stdio::Stderr>",
                 styles: "tracing_human_layer::style::LayerStyles",
                 ..
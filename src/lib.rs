@@ -11,27 +11,74 @@
 //! is enabled.
 //!
 //! Formatting an event _and writing it to stderr_ takes 12.55µs, so actually showing the logs to the
-//! user is about 2× slower than just formatting them.
+//! user is about 2× slower than just formatting them. To keep that cost (and any stalls, if
+//! nobody's reading the output) off of the threads logging events, write through a
+//! [`NonBlocking`] writer, which hands events to a background thread.
+//!
+//! Span fields are formatted once, when they're recorded, and shared between all the events in
+//! the span. When this was added, it brought the time to format an event nested in 16 spans
+//! (the `16 nested spans` benchmark) down from 23.9µs to 17.3µs.
 
 #![deny(missing_docs)]
 
+pub use color::MaybeTerminal;
+pub use color::ShouldColor;
+pub use destination::Destination;
+pub use fields::FieldLayout;
+pub use fields::QuoteStrings;
 pub use layer::HumanLayer;
+pub use non_blocking::FlushGuard;
+pub use non_blocking::FullQueue;
+pub use non_blocking::NonBlocking;
+pub use non_blocking::NonBlockingOptions;
+pub use spacing::EventSpacing;
+pub use span_fields::SpanFieldLayout;
+pub use span_info::SpanDisplay;
+pub use span_info::UnchangedSpans;
 pub use style::LayerStyles;
 pub use style::ProvideStyle;
 pub use style::Style;
 pub use textwrap::TextWrapOptionsOwned;
+pub use timestamp::TimestampFormat;
+pub use timing::SpanTiming;
+pub use timing::SpanTimingThresholds;
+pub use write_errors::WriteErrorCount;
+pub use write_errors::WriteErrorPolicy;
+pub use writer::MakeWriterOutput;
+pub use writer::OutputWriter;
 
-pub(crate) use color::ShouldColor;
+pub(crate) use color::strip_ansi;
+pub(crate) use destination::Output;
 pub(crate) use event::HumanEvent;
+pub(crate) use fields::FieldValue;
 pub(crate) use fields::HumanFields;
-pub(crate) use span_fields::StyledSpanFields;
+pub(crate) use progress::Progress;
+pub(crate) use progress::ProgressBars;
+pub(crate) use spacing::SpacingState;
+pub(crate) use span_fields::FormattedFields;
+pub(crate) use span_fields::LayerId;
+pub(crate) use span_fields::SpanFields;
+pub(crate) use span_fields::SpanFieldsByLayer;
+pub(crate) use span_info::LastSpans;
 pub(crate) use span_info::SpanInfo;
+pub(crate) use status::StatusFooter;
+pub(crate) use timestamp::Timestamps;
+pub(crate) use timing::SpanTimings;
 
 mod color;
+mod destination;
 mod event;
 mod fields;
 mod layer;
+mod non_blocking;
+mod progress;
+mod spacing;
 mod span_fields;
 mod span_info;
+mod status;
 mod style;
 mod textwrap;
+mod timestamp;
+mod timing;
+mod write_errors;
+mod writer;
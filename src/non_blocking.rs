@@ -0,0 +1,579 @@
+//! Writing output on a background thread.
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::Write;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use parking_lot::Condvar;
+use parking_lot::Mutex;
+
+use crate::MaybeTerminal;
+
+#[cfg(doc)]
+use crate::HumanLayer;
+#[cfg(doc)]
+use crate::WriteErrorPolicy;
+
+/// What a [`NonBlocking`] writer does when its queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FullQueue {
+    /// Wait for the background thread to make room in the queue.
+    ///
+    /// Nothing is lost, but a stalled writer (like a pipe nobody is reading) stalls the threads
+    /// writing events.
+    #[default]
+    Block,
+    /// Drop the event being written.
+    DropNewest,
+    /// Drop the oldest event in the queue to make room for the event being written.
+    DropOldest,
+}
+
+/// Options for a [`NonBlocking`] writer.
+#[derive(Debug, Clone)]
+pub struct NonBlockingOptions {
+    /// How many writes can be queued for the background thread.
+    capacity: usize,
+    /// What to do when the queue is full.
+    full_queue: FullQueue,
+}
+
+impl NonBlockingOptions {
+    /// Construct the default options, which queue up to 1024 writes and block when the queue is
+    /// full.
+    pub fn new() -> Self {
+        Self {
+            capacity: 1024,
+            full_queue: FullQueue::default(),
+        }
+    }
+
+    /// Set how many writes can be queued for the background thread.
+    ///
+    /// A [`HumanLayer`] writes each event at once, so this is roughly the number of events.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Set what to do when the queue is full.
+    pub fn with_full_queue(mut self, full_queue: FullQueue) -> Self {
+        self.full_queue = full_queue;
+        self
+    }
+
+    /// Start a background thread writing to the given writer.
+    ///
+    /// The returned [`FlushGuard`] stops the thread when it's dropped, after writing everything
+    /// still in the queue. Keep it around until the program is done logging.
+    ///
+    /// [`MaybeTerminal`] is used to check whether the writer is connected to a terminal; for
+    /// writers which don't implement it, use [`NonBlockingOptions::spawn_terminal`].
+    pub fn spawn<W>(self, writer: W) -> (NonBlocking, FlushGuard)
+    where
+        W: Write + MaybeTerminal + Send + 'static,
+    {
+        let is_terminal = writer.is_terminal();
+        self.spawn_terminal(writer, is_terminal)
+    }
+
+    /// Start a background thread writing to the given writer, and whether it's connected to a
+    /// terminal.
+    ///
+    /// This works for any writer, including ones which don't implement [`MaybeTerminal`].
+    pub fn spawn_terminal<W>(self, writer: W, is_terminal: bool) -> (NonBlocking, FlushGuard)
+    where
+        W: Write + Send + 'static,
+    {
+        let shared = Arc::new(Shared::new(self.capacity));
+        let worker = {
+            let shared = shared.clone();
+            std::thread::Builder::new()
+                .name("tracing-human-layer".to_owned())
+                .spawn(move || work(writer, shared))
+                .expect("Failed to spawn the background writer thread")
+        };
+        (
+            NonBlocking {
+                full_queue: self.full_queue,
+                is_terminal,
+                shared: shared.clone(),
+            },
+            FlushGuard {
+                shared,
+                worker: Some(worker),
+            },
+        )
+    }
+}
+
+impl Default for NonBlockingOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A writer which hands writes to a background thread through a bounded queue, so that
+/// threads writing events don't wait on the output.
+///
+/// Use it as a [`HumanLayer`]'s output writer:
+///
+/// ```
+/// # use tracing_human_layer::HumanLayer;
+/// # use tracing_human_layer::NonBlocking;
+/// let (writer, _guard) = NonBlocking::new(std::io::stderr());
+/// let layer = HumanLayer::new().with_output_writer_detect(writer);
+/// ```
+///
+/// If events are dropped because the queue is full, a notice like `⚠ 3 events dropped` is
+/// written in their place.
+///
+/// Errors writing to the underlying writer happen on the background thread, so they can't be
+/// returned from the write which caused them. Instead, once a write fails, nothing more is
+/// written, and every later write fails with the same error, so that the [`HumanLayer`]'s
+/// [`WriteErrorPolicy`] still applies.
+#[derive(Debug)]
+pub struct NonBlocking {
+    full_queue: FullQueue,
+    /// Whether the underlying writer is connected to a terminal.
+    is_terminal: bool,
+    shared: Arc<Shared>,
+}
+
+impl NonBlocking {
+    /// Start a background thread writing to the given writer, with the default
+    /// [`NonBlockingOptions`].
+    pub fn new<W>(writer: W) -> (Self, FlushGuard)
+    where
+        W: Write + MaybeTerminal + Send + 'static,
+    {
+        NonBlockingOptions::new().spawn(writer)
+    }
+
+    /// Start a background thread writing to the given writer, and whether it's connected to a
+    /// terminal, with the default [`NonBlockingOptions`].
+    ///
+    /// This works for any writer, including ones which don't implement [`MaybeTerminal`].
+    pub fn new_terminal<W>(writer: W, is_terminal: bool) -> (Self, FlushGuard)
+    where
+        W: Write + Send + 'static,
+    {
+        NonBlockingOptions::new().spawn_terminal(writer, is_terminal)
+    }
+
+    fn send(&self, message: Message) -> io::Result<()> {
+        if self.shared.shut_down.load(Ordering::Acquire) {
+            return Err(shut_down());
+        }
+        if let Some(error) = &*self.shared.error.lock() {
+            return Err(io::Error::new(error.kind(), error.to_string()));
+        }
+        self.shared.send(message, self.full_queue)
+    }
+}
+
+impl Write for NonBlocking {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.send(Message::Write(buf.to_vec()))?;
+        Ok(buf.len())
+    }
+
+    /// Ask the background thread to flush the underlying writer, without waiting for it.
+    fn flush(&mut self) -> io::Result<()> {
+        self.send(Message::Flush)
+    }
+}
+
+impl MaybeTerminal for NonBlocking {
+    fn is_terminal(&self) -> bool {
+        self.is_terminal
+    }
+}
+
+/// Stops a [`NonBlocking`] writer's background thread when dropped, after writing and flushing
+/// everything still in the queue.
+///
+/// Writes after the guard is dropped fail.
+#[derive(Debug)]
+#[must_use = "The background thread is stopped when the guard is dropped"]
+pub struct FlushGuard {
+    shared: Arc<Shared>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl FlushGuard {
+    /// Stop the background thread, after writing and flushing everything still in the queue.
+    ///
+    /// This is the same as dropping the guard, but reads a bit clearer.
+    pub fn shutdown(self) {}
+}
+
+impl Drop for FlushGuard {
+    fn drop(&mut self) {
+        self.shared.shut_down.store(true, Ordering::Release);
+        // Control messages are queued even if the queue is full, and never dropped, so the
+        // background thread always gets this.
+        let _ = self.shared.send(Message::Shutdown, FullQueue::Block);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Message {
+    Write(Vec<u8>),
+    Flush,
+    Shutdown,
+}
+
+/// State shared between a [`NonBlocking`] writer, its [`FlushGuard`], and its background thread.
+#[derive(Debug)]
+struct Shared {
+    /// Set when the [`FlushGuard`] is dropped.
+    shut_down: AtomicBool,
+    queue: Mutex<Queue>,
+    /// How many writes can be queued.
+    capacity: usize,
+    /// Signalled when a message is queued.
+    not_empty: Condvar,
+    /// Signalled when a write is taken from the queue, or when the background thread is gone.
+    not_full: Condvar,
+    /// The first error writing to the underlying writer, which later writes fail with.
+    error: Mutex<Option<io::Error>>,
+    /// Writes dropped for [`FullQueue::DropNewest`] since the queue was last empty.
+    dropped_newest: AtomicUsize,
+    /// Writes dropped for [`FullQueue::DropOldest`] since the last write.
+    dropped_oldest: AtomicUsize,
+}
+
+/// The messages waiting for the background thread.
+#[derive(Debug, Default)]
+struct Queue {
+    messages: VecDeque<Message>,
+    /// How many of the `messages` are writes. Only writes count towards the capacity, so
+    /// flushes and shutdowns can always be queued.
+    writes: usize,
+    /// Set when the background thread stops reading the queue, either because it's shut down or
+    /// because the writer panicked.
+    ///
+    /// This is set while holding the lock, so once the background thread has set it, it can
+    /// drain the queue knowing nothing else will be added to it.
+    worker_gone: bool,
+}
+
+impl Shared {
+    fn new(capacity: usize) -> Self {
+        Self {
+            shut_down: AtomicBool::new(false),
+            queue: Mutex::new(Queue::default()),
+            capacity,
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            error: Mutex::new(None),
+            dropped_newest: AtomicUsize::new(0),
+            dropped_oldest: AtomicUsize::new(0),
+        }
+    }
+
+    /// Queue a message for the background thread, handling a full queue with `full_queue`.
+    ///
+    /// Only writes are ever dropped, or wait for room in the queue.
+    fn send(&self, message: Message, full_queue: FullQueue) -> io::Result<()> {
+        let mut queue = self.queue.lock();
+        if queue.worker_gone {
+            return Err(shut_down());
+        }
+        let is_write = matches!(message, Message::Write(_));
+        if is_write && queue.writes >= self.capacity {
+            match full_queue {
+                FullQueue::Block => {
+                    while queue.writes >= self.capacity {
+                        self.not_full.wait(&mut queue);
+                        if queue.worker_gone {
+                            return Err(shut_down());
+                        }
+                    }
+                }
+                FullQueue::DropNewest => {
+                    self.dropped_newest.fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+                FullQueue::DropOldest => {
+                    // The queue is full, so there's at least one write in it.
+                    if let Some(oldest) = queue
+                        .messages
+                        .iter()
+                        .position(|message| matches!(message, Message::Write(_)))
+                    {
+                        queue.messages.remove(oldest);
+                        queue.writes -= 1;
+                        self.dropped_oldest.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+        queue.writes += usize::from(is_write);
+        queue.messages.push_back(message);
+        self.not_empty.notify_one();
+        Ok(())
+    }
+
+    /// Wait for the next message.
+    fn recv(&self) -> Message {
+        let mut queue = self.queue.lock();
+        loop {
+            if let Some(message) = queue.messages.pop_front() {
+                if matches!(message, Message::Write(_)) {
+                    queue.writes -= 1;
+                    self.not_full.notify_one();
+                }
+                return message;
+            }
+            self.not_empty.wait(&mut queue);
+        }
+    }
+
+    /// Mark the background thread as gone, and take everything still in the queue.
+    fn close(&self) -> VecDeque<Message> {
+        let mut queue = self.queue.lock();
+        queue.worker_gone = true;
+        queue.writes = 0;
+        // Wake up any writes waiting for room, so they fail instead of waiting forever.
+        self.not_full.notify_all();
+        std::mem::take(&mut queue.messages)
+    }
+
+    /// Keep the first error writing to the underlying writer.
+    fn check(&self, result: io::Result<()>) {
+        if let Err(error) = result {
+            self.error.lock().get_or_insert(error);
+        }
+    }
+}
+
+fn shut_down() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::BrokenPipe,
+        "The background writer thread has shut down",
+    )
+}
+
+/// Marks the background thread as gone when it exits, even if the writer panics.
+struct WorkerGone<'a>(&'a Shared);
+
+impl Drop for WorkerGone<'_> {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+fn work(mut writer: impl Write, shared: Arc<Shared>) {
+    let _worker_gone = WorkerGone(&shared);
+    let write_dropped = |writer: &mut dyn Write, dropped: &AtomicUsize| {
+        let dropped = dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            let s = if dropped == 1 { "" } else { "s" };
+            let _ = writeln!(writer, "⚠ {dropped} event{s} dropped");
+        }
+    };
+
+    loop {
+        let message = shared.recv();
+        match message {
+            // Writes queued before the first error was noticed are dropped along with the
+            // writes after it.
+            Message::Write(_) | Message::Flush if shared.error.lock().is_some() => {}
+            Message::Write(bytes) => {
+                // The oldest writes were dropped from the queue, just before this one.
+                write_dropped(&mut writer, &shared.dropped_oldest);
+                shared.check(writer.write_all(&bytes));
+                // The newest writes were dropped when the queue was full, after everything
+                // which is still in it.
+                if shared.queue.lock().messages.is_empty() {
+                    write_dropped(&mut writer, &shared.dropped_newest);
+                }
+            }
+            Message::Flush => {
+                shared.check(writer.flush());
+            }
+            Message::Shutdown => break,
+        }
+    }
+
+    // Write anything queued just before the `FlushGuard` was dropped.
+    for message in shared.close() {
+        if let Message::Write(bytes) = message {
+            if shared.error.lock().is_some() {
+                break;
+            }
+            write_dropped(&mut writer, &shared.dropped_oldest);
+            shared.check(writer.write_all(&bytes));
+        }
+    }
+
+    write_dropped(&mut writer, &shared.dropped_oldest);
+    write_dropped(&mut writer, &shared.dropped_newest);
+    let _ = writer.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    /// A writer which waits for permission before each write.
+    #[derive(Clone)]
+    struct GatedWriter {
+        output: Arc<Mutex<Vec<u8>>>,
+        started: mpsc::SyncSender<()>,
+        proceed: Arc<Mutex<mpsc::Receiver<()>>>,
+    }
+
+    impl Write for GatedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let _ = self.started.try_send(());
+            let _ = self.proceed.lock().recv();
+            self.output.lock().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl MaybeTerminal for GatedWriter {}
+
+    /// Write `count` lines through a [`NonBlocking`] writer with room for 2 writes in its
+    /// queue, while the first write is stuck.
+    fn write_full(full_queue: FullQueue, count: usize) -> String {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let (started, started_receiver) = mpsc::sync_channel(1);
+        let (proceed, proceed_receiver) = mpsc::channel();
+        let gated = GatedWriter {
+            output: output.clone(),
+            started,
+            proceed: Arc::new(Mutex::new(proceed_receiver)),
+        };
+
+        let (mut writer, guard) = NonBlockingOptions::new()
+            .with_capacity(2)
+            .with_full_queue(full_queue)
+            .spawn(gated);
+        for i in 1..=count {
+            writer.write_all(format!("{i}\n").as_bytes()).unwrap();
+            if i == 1 {
+                // Wait until the background thread is stuck writing the first line.
+                started_receiver.recv().unwrap();
+            }
+        }
+        // Let every write through.
+        drop(proceed);
+        guard.shutdown();
+
+        let output = output.lock();
+        String::from_utf8(output.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_block() {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let (mut writer, guard) = NonBlocking::new(TestOutput(output.clone()));
+        for i in 1..=100 {
+            writer.write_all(format!("{i}\n").as_bytes()).unwrap();
+        }
+        guard.shutdown();
+
+        let expected = (1..=100).map(|i| format!("{i}\n")).collect::<String>();
+        assert_eq!(String::from_utf8(output.lock().clone()).unwrap(), expected);
+        // Writing after the guard is dropped fails.
+        assert_eq!(
+            writer.write_all(b"101\n").unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+    }
+
+    #[test]
+    fn test_drop_newest() {
+        assert_eq!(
+            write_full(FullQueue::DropNewest, 6),
+            "1\n2\n3\n⚠ 3 events dropped\n"
+        );
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        assert_eq!(
+            write_full(FullQueue::DropOldest, 6),
+            "1\n⚠ 3 events dropped\n5\n6\n"
+        );
+    }
+
+    #[test]
+    fn test_writer_panics() {
+        struct PanickingWriter;
+
+        impl Write for PanickingWriter {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                panic!("Failed to write");
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // The queue fills up once the background thread is gone, but writes fail instead of
+        // waiting for room forever.
+        let (mut writer, guard) = NonBlockingOptions::new()
+            .with_capacity(1)
+            .spawn_terminal(PanickingWriter, false);
+        let error = (0..10)
+            .find_map(|_| writer.write_all(b"line\n").err())
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+        guard.shutdown();
+    }
+
+    #[test]
+    fn test_drop_oldest_keeps_shutdown() {
+        // Flood the queue while the guard is dropped, so that the `Shutdown` message is queued
+        // while writes are making room for themselves.
+        for _ in 0..100 {
+            let (mut writer, guard) = NonBlockingOptions::new()
+                .with_capacity(1)
+                .with_full_queue(FullQueue::DropOldest)
+                .spawn(io::sink());
+            let producer = std::thread::spawn(move || while writer.write_all(b"line\n").is_ok() {});
+            let (dropped, dropped_receiver) = mpsc::channel();
+            std::thread::spawn(move || {
+                guard.shutdown();
+                let _ = dropped.send(());
+            });
+            dropped_receiver
+                .recv_timeout(std::time::Duration::from_secs(10))
+                .expect("The guard should return after the background thread stops");
+            producer.join().unwrap();
+        }
+    }
+
+    struct TestOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for TestOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl MaybeTerminal for TestOutput {}
+}
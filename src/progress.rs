@@ -0,0 +1,246 @@
+//! Progress bars for spans with `progress.current` and `progress.total` fields.
+
+use std::fmt;
+use std::fmt::Display;
+use std::time::Duration;
+use std::time::Instant;
+
+use owo_colors::Style as OwoStyle;
+use textwrap::core::display_width;
+use tracing::field::FieldSet;
+use tracing::span::Id;
+
+use crate::style::IntoConditionalColor;
+use crate::FieldValue;
+use crate::HumanFields;
+use crate::ShouldColor;
+use crate::Style;
+
+#[cfg(doc)]
+use crate::HumanLayer;
+
+/// The field holding a span's current progress.
+const CURRENT: &str = "progress.current";
+/// The field holding the total progress a span will make, if it's known.
+const TOTAL: &str = "progress.total";
+
+/// Frames for the spinner shown when a span's total progress is unknown.
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// The widest a progress bar gets.
+const MAX_BAR_WIDTH: usize = 30;
+
+/// The narrowest a progress bar gets before it's left out.
+const MIN_BAR_WIDTH: usize = 5;
+
+/// A span's progress, read from its `progress.current` and `progress.total` fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Progress {
+    pub(crate) current: u64,
+    pub(crate) total: Option<u64>,
+}
+
+impl Progress {
+    /// Does a span declare any progress fields?
+    pub(crate) fn is_declared(field_set: &FieldSet) -> bool {
+        field_set.field(CURRENT).is_some() || field_set.field(TOTAL).is_some()
+    }
+
+    /// Read a span's progress from its fields.
+    ///
+    /// Fields which haven't been recorded yet, or which aren't integers, are treated as
+    /// missing.
+    pub(crate) fn from_fields(fields: &HumanFields) -> Self {
+        let mut progress = Self {
+            current: 0,
+            total: None,
+        };
+        for (name, value) in &fields.fields {
+            let value = match value {
+                FieldValue::U64(value) => Some(*value),
+                FieldValue::I64(value) => u64::try_from(*value).ok(),
+                _ => None,
+            };
+            match *name {
+                CURRENT => progress.current = value.unwrap_or_default(),
+                TOTAL => progress.total = value,
+                _ => {}
+            }
+        }
+        progress
+    }
+
+    fn percent(&self) -> Option<u64> {
+        self.total.map(|total| match total {
+            0 => 100,
+            _ => (self.current.min(total) * 100) / total,
+        })
+    }
+}
+
+/// Formats progress for plain output, like `42/100 (42%)`, or just `42` if the total is
+/// unknown.
+impl Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.total, self.percent()) {
+            (Some(total), Some(percent)) => {
+                write!(f, "{}/{total} ({percent}%)", self.current)
+            }
+            _ => write!(f, "{}", self.current),
+        }
+    }
+}
+
+/// The progress bars for the open spans with progress, drawn below the rest of the output
+/// on an interactive terminal.
+///
+/// This is kept behind the same lock as the writer, so that the bars can be erased before an
+/// event is written and drawn again after it.
+#[derive(Debug, Default)]
+pub(crate) struct ProgressBars {
+    bars: Vec<ProgressBar>,
+}
+
+#[derive(Debug)]
+struct ProgressBar {
+    id: Id,
+    name: &'static str,
+    progress: Progress,
+    /// Style for the span's name.
+    name_style: OwoStyle,
+    /// Style for the bar or spinner.
+    bar_style: OwoStyle,
+    /// The current frame of the spinner, if the total is unknown.
+    spinner: usize,
+    /// When progress was last written as a plain line, if the output isn't a terminal.
+    last_printed: Option<Instant>,
+}
+
+impl ProgressBars {
+    /// Add or update a span's progress bar.
+    pub(crate) fn update(
+        &mut self,
+        id: &Id,
+        name: &'static str,
+        progress: Progress,
+        style: &Style,
+    ) {
+        match self.bars.iter_mut().find(|bar| bar.id == *id) {
+            Some(bar) => {
+                bar.progress = progress;
+                bar.spinner = (bar.spinner + 1) % SPINNER.len();
+            }
+            None => self.bars.push(ProgressBar {
+                id: id.clone(),
+                name,
+                progress,
+                name_style: style.span_name,
+                bar_style: style.initial_indent,
+                spinner: 0,
+                last_printed: None,
+            }),
+        }
+    }
+
+    /// Remove a span's progress bar, returning whether it had one.
+    pub(crate) fn remove(&mut self, id: &Id) -> bool {
+        let len = self.bars.len();
+        self.bars.retain(|bar| bar.id != *id);
+        self.bars.len() != len
+    }
+
+    /// Should a span's progress be written as a plain line? This is true at most once every
+    /// `interval` for each span.
+    pub(crate) fn should_print(&mut self, id: &Id, interval: Duration) -> bool {
+        let Some(bar) = self.bars.iter_mut().find(|bar| bar.id == *id) else {
+            return false;
+        };
+        let now = Instant::now();
+        let should_print = bar
+            .last_printed
+            .is_none_or(|last| now.saturating_duration_since(last) >= interval);
+        if should_print {
+            bar.last_printed = Some(now);
+        }
+        should_print
+    }
+
+    /// Format the progress bars, one per line, each fitting in `width` columns.
+    pub(crate) fn lines(
+        &self,
+        width: usize,
+        color: ShouldColor,
+    ) -> impl Iterator<Item = String> + '_ {
+        self.bars.iter().map(move |bar| bar.line(width, color))
+    }
+}
+
+impl ProgressBar {
+    /// Format the bar, like `download ███████░░░░░░░ 42/100 42%`, or `⠋ download 42` if the
+    /// total is unknown.
+    fn line(&self, width: usize, color: ShouldColor) -> String {
+        let name = self.name.colored(color, self.name_style);
+        let (Some(total), Some(percent)) = (self.progress.total, self.progress.percent()) else {
+            return format!(
+                "{} {name} {}",
+                SPINNER[self.spinner].colored(color, self.bar_style),
+                self.progress.current,
+            );
+        };
+
+        let counts = format!("{}/{total} {percent:>3}%", self.progress.current);
+        // The name, the bar, and the counts, separated by spaces.
+        let bar_width = width
+            .saturating_sub(display_width(self.name) + display_width(&counts) + 2)
+            .min(MAX_BAR_WIDTH);
+        if bar_width < MIN_BAR_WIDTH {
+            return format!("{name} {counts}");
+        }
+
+        let filled = (bar_width as u64 * percent / 100) as usize;
+        let bar = format!("{}{}", "█".repeat(filled), "░".repeat(bar_width - filled));
+        format!("{name} {} {counts}", bar.colored(color, self.bar_style))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(current: u64, total: Option<u64>) -> ProgressBar {
+        ProgressBar {
+            id: Id::from_u64(1),
+            name: "download",
+            progress: Progress { current, total },
+            name_style: OwoStyle::new(),
+            bar_style: OwoStyle::new(),
+            spinner: 0,
+            last_printed: None,
+        }
+    }
+
+    #[test]
+    fn test_progress_display() {
+        let progress = |current, total| Progress { current, total }.to_string();
+        assert_eq!(progress(42, Some(100)), "42/100 (42%)");
+        assert_eq!(progress(3, Some(0)), "3/0 (100%)");
+        assert_eq!(progress(42, None), "42");
+    }
+
+    #[test]
+    fn test_bar_line() {
+        let line = |bar: ProgressBar, width| bar.line(width, ShouldColor::Never);
+        assert_eq!(
+            line(bar(50, Some(100)), 80),
+            "download ███████████████░░░░░░░░░░░░░░░ 50/100  50%"
+        );
+        // The bar shrinks to fit.
+        assert_eq!(
+            line(bar(50, Some(100)), 30),
+            "download ████░░░░░ 50/100  50%"
+        );
+        // Or is left out entirely.
+        assert_eq!(line(bar(50, Some(100)), 20), "download 50/100  50%");
+        assert_eq!(line(bar(50, None), 80), "⠋ download 50");
+    }
+}
@@ -0,0 +1,145 @@
+//! Blank lines between events.
+
+use tracing::span::Id;
+
+#[cfg(doc)]
+use crate::HumanLayer;
+
+/// When to print blank lines between events, to help visually separate them.
+///
+/// See [`HumanLayer::with_event_spacing`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EventSpacing {
+    /// Never print blank lines between events.
+    Never,
+    /// Print blank lines before and after events with messages longer than one line.
+    ///
+    /// If two long events are printed one after another, only one blank line is printed between
+    /// them.
+    #[default]
+    AroundLongEvents,
+    /// Print a blank line between every event.
+    Always,
+    /// Print a blank line between events in different top-level spans, so that each top-level
+    /// span's events are grouped together.
+    BetweenRootSpans,
+}
+
+/// Blank lines to print before and after an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Spacing {
+    pub(crate) before: bool,
+    pub(crate) after: bool,
+}
+
+/// What we need to remember about the previous event to decide on [`Spacing`].
+///
+/// This is kept behind the same lock as the writer, so that the spacing decision and the write
+/// happen atomically.
+#[derive(Debug, Default)]
+pub(crate) struct SpacingState {
+    /// Has any event been written yet?
+    wrote_any: bool,
+    /// Was a blank line printed after the previous event?
+    blank_after: bool,
+    /// The root span of the previous event, if any.
+    root_span: Option<Id>,
+}
+
+impl EventSpacing {
+    /// Decide on the [`Spacing`] for an event and remember it for the next event.
+    ///
+    /// An event `is_long` if its message takes more than one line.
+    pub(crate) fn next(
+        self,
+        state: &mut SpacingState,
+        is_long: bool,
+        root_span: Option<&Id>,
+    ) -> Spacing {
+        let spacing = match self {
+            EventSpacing::Never => Spacing {
+                before: false,
+                after: false,
+            },
+            EventSpacing::AroundLongEvents => Spacing {
+                before: is_long && !state.blank_after,
+                after: is_long,
+            },
+            EventSpacing::Always => Spacing {
+                before: state.wrote_any,
+                after: false,
+            },
+            EventSpacing::BetweenRootSpans => Spacing {
+                before: state.wrote_any && state.root_span.as_ref() != root_span,
+                after: false,
+            },
+        };
+
+        state.wrote_any = true;
+        state.blank_after = spacing.after;
+        state.root_span = root_span.cloned();
+        spacing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Get the spacing for a sequence of events, given as `(is_long, root_span)` pairs.
+    fn spacing(policy: EventSpacing, events: &[(bool, Option<u64>)]) -> Vec<(bool, bool)> {
+        let mut state = SpacingState::default();
+        events
+            .iter()
+            .map(|(is_long, root_span)| {
+                let root_span = root_span.map(Id::from_u64);
+                let spacing = policy.next(&mut state, *is_long, root_span.as_ref());
+                (spacing.before, spacing.after)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_never() {
+        assert_eq!(
+            spacing(EventSpacing::Never, &[(true, None), (false, None)]),
+            [(false, false), (false, false)]
+        );
+    }
+
+    #[test]
+    fn test_around_long_events() {
+        assert_eq!(
+            spacing(
+                EventSpacing::AroundLongEvents,
+                &[(false, None), (true, None), (true, None), (false, None)]
+            ),
+            // Only one blank line between two long events.
+            [(false, false), (true, true), (false, true), (false, false)]
+        );
+    }
+
+    #[test]
+    fn test_always() {
+        assert_eq!(
+            spacing(EventSpacing::Always, &[(false, None), (true, None)]),
+            [(false, false), (true, false)]
+        );
+    }
+
+    #[test]
+    fn test_between_root_spans() {
+        assert_eq!(
+            spacing(
+                EventSpacing::BetweenRootSpans,
+                &[
+                    (false, Some(1)),
+                    (false, Some(1)),
+                    (false, Some(2)),
+                    (false, None)
+                ]
+            ),
+            [(false, false), (false, false), (true, false), (true, false)]
+        );
+    }
+}
@@ -1,34 +1,207 @@
-use std::borrow::Cow;
-use std::fmt;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
-use itertools::Itertools;
+use tracing::field::FieldSet;
+use tracing_subscriber::registry::ExtensionsMut;
 
-use crate::style::IntoConditionalColor;
 use crate::HumanFields;
 use crate::ShouldColor;
 use crate::Style;
 
+/// How to lay out a span's fields when they don't fit on one line.
+///
+/// See [`crate::HumanLayer::with_span_field_layout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpanFieldLayout {
+    /// Break lines between fields, putting as many fields as fit on each line:
+    ///
+    /// ```text
+    /// in copy{from=/home/puppy/photos/2024 to=/mnt/backup/photos/2024
+    ///    overwrite=false}
+    /// ```
+    #[default]
+    Wrap,
+    /// Put each field on a separate line:
+    ///
+    /// ```text
+    /// in copy{from=/home/puppy/photos/2024
+    ///    to=/mnt/backup/photos/2024
+    ///    overwrite=false}
+    /// ```
+    OnePerLine,
+}
+
+/// Identifies a [`crate::HumanLayer`], so that several layers in one subscriber can store
+/// their own [`SpanFields`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayerId(usize);
+
+impl LayerId {
+    pub fn new() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// The [`SpanFields`] for each [`crate::HumanLayer`] in a subscriber, stored in a span's
+/// extensions.
+///
+/// Layers may be configured with different colors and styles, so they can't share formatted
+/// fields.
+#[derive(Debug, Default)]
+pub struct SpanFieldsByLayer(Vec<(LayerId, SpanFields)>);
+
+impl SpanFieldsByLayer {
+    pub fn get(&self, layer: LayerId) -> Option<&SpanFields> {
+        self.0
+            .iter()
+            .find(|(id, _)| *id == layer)
+            .map(|(_, fields)| fields)
+    }
+
+    pub fn get_mut(&mut self, layer: LayerId) -> Option<&mut SpanFields> {
+        self.0
+            .iter_mut()
+            .find(|(id, _)| *id == layer)
+            .map(|(_, fields)| fields)
+    }
+
+    pub fn insert(&mut self, layer: LayerId, fields: SpanFields) {
+        match self.get_mut(layer) {
+            Some(existing) => *existing = fields,
+            None => self.0.push((layer, fields)),
+        }
+    }
+
+    /// Insert a layer's [`SpanFields`] into a span's extensions.
+    pub fn insert_into(extensions: &mut ExtensionsMut<'_>, layer: LayerId, fields: SpanFields) {
+        match extensions.get_mut::<Self>() {
+            Some(by_layer) => by_layer.insert(layer, fields),
+            None => {
+                let mut by_layer = Self::default();
+                by_layer.insert(layer, fields);
+                extensions.insert(by_layer);
+            }
+        }
+    }
+}
+
+/// A span's fields, stored in its extensions by a [`crate::HumanLayer`].
 #[derive(Debug)]
-pub struct StyledSpanFields<'a> {
-    pub(crate) style: Cow<'a, Style>,
+pub struct SpanFields {
+    /// The fields recorded so far.
     pub(crate) fields: HumanFields,
-    pub(crate) color: ShouldColor,
-}
-
-impl<'a> fmt::Display for StyledSpanFields<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if !self.fields.is_empty() {
-            write!(
-                f,
-                "{}{}{}",
-                "{".colored(self.color, self.style.span_name),
-                self.fields
-                    .iter()
-                    .map(|(name, value)| self.style.style_field(self.color, name, value))
-                    .join(" "),
-                "}".colored(self.color, self.style.span_name)
-            )?;
+    /// The `fields`, each formatted like `name=value`.
+    pub(crate) formatted: FormattedFields,
+    /// Whether the span has been entered, and a header has been written for it if spans are
+    /// shown as a tree.
+    pub(crate) entered: bool,
+}
+
+impl SpanFields {
+    pub fn new(
+        fields: HumanFields,
+        style: &Style,
+        colors: impl IntoIterator<Item = ShouldColor>,
+    ) -> Self {
+        let formatted = FormattedFields::new(&fields, style, colors);
+        Self {
+            fields,
+            formatted,
+            entered: false,
+        }
+    }
+
+    /// Merge newly-recorded fields into these fields and re-format them.
+    pub fn record(
+        &mut self,
+        recorded: HumanFields,
+        field_set: &FieldSet,
+        style: &Style,
+        colors: impl IntoIterator<Item = ShouldColor>,
+    ) {
+        self.fields.merge(recorded, field_set);
+        self.formatted = FormattedFields::new(&self.fields, style, colors);
+    }
+}
+
+/// A span's fields, each formatted like `name=value`, with and without color.
+///
+/// These are shared with every event in the span, so they're reference-counted rather than
+/// copied for each event. The fields are kept separate so that events can break lines between
+/// them.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FormattedFields {
+    colored: Option<Arc<[String]>>,
+    plain: Option<Arc<[String]>>,
+}
+
+impl FormattedFields {
+    /// Format the fields once for each of the `colors` they're written in.
+    pub(crate) fn new(
+        fields: &HumanFields,
+        style: &Style,
+        colors: impl IntoIterator<Item = ShouldColor>,
+    ) -> Self {
+        let format = |color| -> Arc<[String]> {
+            fields
+                .fields
+                .iter()
+                .map(|(name, value)| {
+                    style
+                        .style_field(color, name, value.display(fields.quote_strings))
+                        .to_string()
+                })
+                .collect()
+        };
+        let mut formatted = Self::default();
+        for color in colors {
+            match color {
+                ShouldColor::Always => {
+                    formatted.colored.get_or_insert_with(|| format(color));
+                }
+                ShouldColor::Never | ShouldColor::Auto => {
+                    formatted
+                        .plain
+                        .get_or_insert_with(|| format(ShouldColor::Never));
+                }
+            }
+        }
+        formatted
+    }
+
+    /// Get the fields formatted with or without color.
+    pub(crate) fn get(&self, color: ShouldColor) -> &[String] {
+        let (preferred, fallback) = match color {
+            ShouldColor::Always => (&self.colored, &self.plain),
+            ShouldColor::Never | ShouldColor::Auto => (&self.plain, &self.colored),
+        };
+        preferred
+            .as_deref()
+            .or(fallback.as_deref())
+            .unwrap_or_default()
+    }
+
+    /// Are these the same fields, rather than fields which were recorded separately?
+    pub(crate) fn ptr_eq(&self, other: &Self) -> bool {
+        fn ptr_eq(a: &Option<Arc<[String]>>, b: &Option<Arc<[String]>>) -> bool {
+            match (a, b) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (None, None) => true,
+                _ => false,
+            }
+        }
+        ptr_eq(&self.colored, &other.colored) && ptr_eq(&self.plain, &other.plain)
+    }
+}
+
+/// Plain fields, for tests.
+impl From<Vec<String>> for FormattedFields {
+    fn from(fields: Vec<String>) -> Self {
+        Self {
+            colored: None,
+            plain: Some(fields.into()),
         }
-        Ok(())
     }
 }
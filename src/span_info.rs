@@ -1,24 +1,95 @@
-use tracing_subscriber::fmt::FormattedFields;
+use tracing::span::Id;
 use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::registry::Scope;
 
-use crate::HumanLayer;
+use crate::FormattedFields;
+use crate::LayerId;
+use crate::SpanFieldsByLayer;
+
+/// Which spans to list after an event, and how.
+///
+/// This is set per level with [`crate::Style::with_span_display`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpanDisplay {
+    /// Every span, innermost first, one per line.
+    #[default]
+    Full,
+    /// Every span, outermost first, one per line.
+    RootFirst,
+    /// Only the innermost span.
+    Leaf,
+    /// Every span on one line, outermost first, with only the innermost span's fields, like
+    /// `in outer › copy › inner{path=a.txt}`.
+    Breadcrumb,
+    /// At most this many spans, innermost first, one per line.
+    MaxDepth(usize),
+    /// No spans.
+    None,
+}
+
+/// What to write instead of an event's spans when they're the same as the previous event's
+/// spans.
+///
+/// See [`crate::HumanLayer::with_unchanged_spans`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnchangedSpans {
+    /// Write the spans again.
+    #[default]
+    Show,
+    /// Don't write the spans.
+    Omit,
+    /// Write a `〃` marker instead of the spans.
+    Mark,
+}
+
+/// The spans of the last event written, used to check if the next event's spans are unchanged.
+///
+/// This is kept behind the same lock as the writer, so that it always matches the output.
+#[derive(Debug, Default)]
+pub(crate) struct LastSpans(Vec<(Id, &'static str, FormattedFields)>);
+
+impl LastSpans {
+    /// Remember the spans of an event being written, and return whether they're the same as
+    /// the last event's spans.
+    ///
+    /// Spans are compared by ID, and their fields are compared by pointer, because they're
+    /// formatted again whenever they're recorded.
+    pub(crate) fn update(&mut self, spans: &[SpanInfo]) -> bool {
+        let unchanged = self.0.len() == spans.len()
+            && self.0.iter().zip(spans).all(|((id, name, fields), span)| {
+                *id == span.id && *name == span.name && fields.ptr_eq(&span.fields)
+            });
+        if !unchanged {
+            self.0.clear();
+            self.0.extend(
+                spans
+                    .iter()
+                    .map(|span| (span.id.clone(), span.name, span.fields.clone())),
+            );
+        }
+        unchanged
+    }
+}
 
 #[derive(Debug)]
 pub struct SpanInfo {
+    /// The span's ID.
+    pub id: Id,
     /// The span's name.
     pub name: &'static str,
-    /// The span's fields, formatted.
-    pub fields: String,
+    /// The span's fields, each formatted like `name=value`.
+    pub fields: FormattedFields,
 }
 
 impl SpanInfo {
     /// Get a list of `SpanInfo`s from a [`Scope`] by traversing its spans from root to leaf
     /// (outside-in).
     ///
-    /// This relies on the [`super::HumanLayer`] to insert formatted fields in the span's
-    /// extensions.
-    pub fn from_scope<S>(scope: Scope<'_, S>) -> Vec<Self>
+    /// This relies on the [`super::HumanLayer`] identified by `layer` to insert formatted fields
+    /// in the span's extensions. If it didn't (for example, if the layer was added with
+    /// [`tracing_subscriber::reload`] after the span was created), only the span's name is
+    /// shown.
+    pub fn from_scope<S>(scope: Scope<'_, S>, layer: LayerId) -> Vec<Self>
     where
         S: tracing::Subscriber,
         S: for<'lookup> LookupSpan<'lookup>,
@@ -26,13 +97,15 @@ impl SpanInfo {
         let mut spans = Vec::new();
         for span in scope.from_root() {
             let extensions = span.extensions();
-            let fields = &extensions
-                .get::<FormattedFields<HumanLayer>>()
-                .expect("A span should always have formatted fields")
-                .fields;
+            let fields = extensions
+                .get::<SpanFieldsByLayer>()
+                .and_then(|by_layer| by_layer.get(layer))
+                .map(|fields| fields.formatted.clone())
+                .unwrap_or_default();
             spans.push(SpanInfo {
+                id: span.id(),
                 name: span.name(),
-                fields: fields.to_owned(),
+                fields,
             });
         }
         spans
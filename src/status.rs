@@ -0,0 +1,225 @@
+//! A status footer listing the spans which are currently entered.
+
+use std::time::Duration;
+use std::time::Instant;
+
+use owo_colors::Style as OwoStyle;
+use textwrap::core::display_width;
+use tracing::span::Id;
+
+use crate::style::IntoConditionalColor;
+use crate::timing::HumanDuration;
+use crate::FormattedFields;
+use crate::ShouldColor;
+use crate::Style;
+
+/// The spans which are currently entered, drawn below the rest of the output on an interactive
+/// terminal, along with how long they've been running.
+///
+/// This is kept behind the same lock as the writer, so that the footer can be erased before an
+/// event is written and drawn again after it.
+#[derive(Debug, Default)]
+pub(crate) struct StatusFooter {
+    spans: Vec<ActiveSpan>,
+}
+
+#[derive(Debug)]
+struct ActiveSpan {
+    id: Id,
+    name: &'static str,
+    /// The span's fields, each formatted like `name=value`.
+    fields: FormattedFields,
+    /// Style for the span's name.
+    name_style: OwoStyle,
+    /// Style for the elapsed time.
+    elapsed_style: OwoStyle,
+    /// When the span was first entered.
+    started: Instant,
+    /// How many threads are currently in the span.
+    entered: usize,
+}
+
+impl StatusFooter {
+    /// Record that a span has been entered, adding it to the footer if it isn't already there.
+    ///
+    /// Returns whether the span was shown, rather than already being entered on another
+    /// thread.
+    pub(crate) fn enter(
+        &mut self,
+        id: &Id,
+        name: &'static str,
+        fields: FormattedFields,
+        style: &Style,
+    ) -> bool {
+        match self.spans.iter_mut().find(|span| span.id == *id) {
+            Some(span) => {
+                span.entered += 1;
+                span.entered == 1
+            }
+            None => {
+                self.spans.push(ActiveSpan {
+                    id: id.clone(),
+                    name,
+                    fields,
+                    name_style: style.span_name,
+                    elapsed_style: style.timestamp,
+                    started: Instant::now(),
+                    entered: 1,
+                });
+                true
+            }
+        }
+    }
+
+    /// Record that a span has been exited, returning whether that hides it from the footer.
+    ///
+    /// The span is kept until it closes, so that its elapsed time includes any time it spends
+    /// idle.
+    pub(crate) fn exit(&mut self, id: &Id) -> bool {
+        match self.spans.iter_mut().find(|span| span.id == *id) {
+            Some(span) => {
+                span.entered = span.entered.saturating_sub(1);
+                span.entered == 0
+            }
+            None => false,
+        }
+    }
+
+    /// Update a span's fields, returning whether it's shown in the footer.
+    pub(crate) fn record(&mut self, id: &Id, fields: FormattedFields) -> bool {
+        match self.spans.iter_mut().find(|span| span.id == *id) {
+            Some(span) => {
+                span.fields = fields;
+                span.entered > 0
+            }
+            None => false,
+        }
+    }
+
+    /// Remove a closed span, returning whether it was in the footer.
+    pub(crate) fn remove(&mut self, id: &Id) -> bool {
+        let len = self.spans.len();
+        self.spans.retain(|span| span.id != *id);
+        self.spans.len() != len
+    }
+
+    /// Format the footer, one line for each span which is entered and has been running for at
+    /// least `threshold`. Each line fits in `width` columns, if the span's name does.
+    pub(crate) fn lines(
+        &self,
+        width: usize,
+        color: ShouldColor,
+        threshold: Duration,
+    ) -> impl Iterator<Item = String> + '_ {
+        let now = Instant::now();
+        self.spans
+            .iter()
+            .filter(move |span| {
+                span.entered > 0 && now.saturating_duration_since(span.started) >= threshold
+            })
+            .map(move |span| span.line(width, color, now.saturating_duration_since(span.started)))
+    }
+}
+
+impl ActiveSpan {
+    /// Format the span, like `build{crate=foo} 1m 05s`.
+    ///
+    /// Fields which don't fit in `width` columns are left out.
+    fn line(&self, width: usize, color: ShouldColor, elapsed: Duration) -> String {
+        // Only whole seconds, so the footer doesn't change every time it's drawn.
+        let elapsed = match elapsed.as_secs() {
+            0 => "0s".to_owned(),
+            secs => HumanDuration(Duration::from_secs(secs)).to_string(),
+        };
+        // The name, the braces, and a space before the elapsed time.
+        let mut line_width = display_width(self.name) + display_width(&elapsed) + 3;
+        let mut fields = String::new();
+        for field in self.fields.get(color) {
+            let field_width = display_width(field) + usize::from(!fields.is_empty());
+            if line_width + field_width > width {
+                break;
+            }
+            if !fields.is_empty() {
+                fields.push(' ');
+            }
+            fields.push_str(field);
+            line_width += field_width;
+        }
+
+        let name = self.name.colored(color, self.name_style);
+        let elapsed = elapsed.colored(color, self.elapsed_style);
+        if fields.is_empty() {
+            format!("{name} {elapsed}")
+        } else {
+            format!("{name}{{{fields}}} {elapsed}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn active_span(fields: &[&str]) -> ActiveSpan {
+        ActiveSpan {
+            id: Id::from_u64(1),
+            name: "build",
+            fields: fields
+                .iter()
+                .map(|field| field.to_string())
+                .collect::<Vec<_>>()
+                .into(),
+            name_style: OwoStyle::new(),
+            elapsed_style: OwoStyle::new(),
+            started: Instant::now(),
+            entered: 1,
+        }
+    }
+
+    #[test]
+    fn test_line() {
+        let line = |span: ActiveSpan, width, secs| {
+            span.line(width, ShouldColor::Never, Duration::from_secs(secs))
+        };
+        assert_eq!(line(active_span(&[]), 80, 0), "build 0s");
+        assert_eq!(
+            line(active_span(&["crate=foo", "jobs=4"]), 80, 65),
+            "build{crate=foo jobs=4} 1m 05s"
+        );
+        // Fields which don't fit are left out.
+        assert_eq!(
+            line(active_span(&["crate=foo", "jobs=4"]), 20, 3),
+            "build{crate=foo} 3s"
+        );
+        assert_eq!(line(active_span(&["crate=foo"]), 10, 3), "build 3s");
+    }
+
+    #[test]
+    fn test_enter_exit() {
+        let style = crate::LayerStyles::new().info;
+        let id = Id::from_u64(1);
+        let mut footer = StatusFooter::default();
+        let lines = |footer: &StatusFooter| {
+            footer
+                .lines(80, ShouldColor::Never, Duration::ZERO)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(footer.enter(&id, "build", Default::default(), &style));
+        assert!(!footer.enter(&id, "build", Default::default(), &style));
+        assert_eq!(lines(&footer), ["build 0s"]);
+        // Still entered on another thread.
+        assert!(!footer.exit(&id));
+        assert_eq!(lines(&footer), ["build 0s"]);
+        assert!(footer.exit(&id));
+        assert!(lines(&footer).is_empty());
+        // Spans are hidden until they've been running long enough.
+        assert!(footer.enter(&id, "build", Default::default(), &style));
+        assert!(footer
+            .lines(80, ShouldColor::Never, Duration::from_secs(60))
+            .next()
+            .is_none());
+        assert!(footer.remove(&id));
+        assert!(lines(&footer).is_empty());
+    }
+}
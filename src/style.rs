@@ -2,13 +2,18 @@ use std::borrow::Cow;
 use std::fmt::Display;
 
 use owo_colors::Style as OwoStyle;
+use textwrap::core::display_width;
 use tracing::Level;
 use tracing::Metadata;
 
 use crate::ShouldColor;
+use crate::SpanDisplay;
+use crate::SpanTiming;
 
 #[cfg(doc)]
 use crate::HumanLayer;
+#[cfg(doc)]
+use tracing_subscriber::fmt::format::FmtSpan;
 
 /// A value that can provide a [`Style`] for a given [`tracing`] event.
 pub trait ProvideStyle {
@@ -17,6 +22,19 @@ pub trait ProvideStyle {
     /// In the future we may want to give implementers access to more information, but all events
     /// contain metadata, so we can provide default implementations for future methods.
     fn for_metadata(&self, metadata: &'static Metadata<'static>) -> Cow<'_, Style>;
+
+    /// Provide a [`Style`] for the event written when a span closes, if
+    /// [`FmtSpan::CLOSE`] events are enabled.
+    ///
+    /// This can be used to highlight slow spans. By default, this is the same as
+    /// [`ProvideStyle::for_metadata`].
+    fn for_span_close(
+        &self,
+        metadata: &'static Metadata<'static>,
+        _timing: SpanTiming,
+    ) -> Cow<'_, Style> {
+        self.for_metadata(metadata)
+    }
 }
 
 /// A simple [`ProvideStyle`] implementation which stores a style for each [`tracing::Level`].
@@ -40,32 +58,43 @@ impl LayerStyles {
         let base = Style {
             initial_indent_text: "".into(),
             subsequent_indent_text: "  ".into(),
+            span_close_text: "✓ ".into(),
             initial_indent: OwoStyle::new(),
             message: OwoStyle::new(),
             field_name: OwoStyle::new().bold(),
             field_value: OwoStyle::new(),
             span_name: OwoStyle::new(),
             span_in: OwoStyle::new().dimmed(),
+            timestamp: OwoStyle::new().dimmed(),
+            error_caused_by: OwoStyle::new().dimmed(),
+            error_source: OwoStyle::new(),
+            span_display: SpanDisplay::Full,
+            tree_guide_text: "│ ".into(),
+            tree_guide: OwoStyle::new().dimmed(),
         };
 
         Self {
             trace: Style {
                 initial_indent_text: "TRACE ".into(),
+                span_close_text: "TRACE ".into(),
                 initial_indent: base.initial_indent.purple(),
                 message: base.message.dimmed(),
                 field_name: base.field_name.dimmed(),
                 field_value: base.field_value.dimmed(),
                 span_name: base.span_name.dimmed(),
+                error_source: base.error_source.dimmed(),
                 ..base.clone()
             },
 
             debug: Style {
                 initial_indent_text: "DEBUG ".into(),
+                span_close_text: "DEBUG ".into(),
                 initial_indent: base.initial_indent.blue(),
                 message: base.message.dimmed(),
                 field_name: base.field_name.dimmed(),
                 field_value: base.field_value.dimmed(),
                 span_name: base.span_name.dimmed(),
+                error_source: base.error_source.dimmed(),
                 ..base.clone()
             },
 
@@ -91,6 +120,20 @@ impl LayerStyles {
         }
     }
 
+    /// Set which spans are listed after events of every level.
+    pub fn with_span_display(mut self, span_display: SpanDisplay) -> Self {
+        for style in [
+            &mut self.trace,
+            &mut self.debug,
+            &mut self.info,
+            &mut self.warn,
+            &mut self.error,
+        ] {
+            style.span_display = span_display;
+        }
+        self
+    }
+
     /// Get the style for a given level.
     pub(crate) fn for_level(&self, level: Level) -> Cow<'_, Style> {
         Cow::Borrowed(match level {
@@ -119,29 +162,37 @@ impl ProvideStyle for LayerStyles {
 ///
 /// A [`HumanLayer`] retrieves styles through a [`ProvideStyle`] implementation.
 ///
-/// TODO: It should be possible to configure which spans and attributes are printed.
+/// TODO: It should be possible to configure which attributes are printed.
 #[derive(Debug, Clone)]
 pub struct Style {
     pub(crate) initial_indent_text: Cow<'static, str>,
     pub(crate) subsequent_indent_text: Cow<'static, str>,
+    pub(crate) span_close_text: Cow<'static, str>,
     pub(crate) initial_indent: OwoStyle,
     pub(crate) message: OwoStyle,
     pub(crate) field_name: OwoStyle,
     pub(crate) field_value: OwoStyle,
     pub(crate) span_name: OwoStyle,
     pub(crate) span_in: OwoStyle,
+    pub(crate) timestamp: OwoStyle,
+    pub(crate) error_caused_by: OwoStyle,
+    pub(crate) error_source: OwoStyle,
+    pub(crate) span_display: SpanDisplay,
+    pub(crate) tree_guide_text: Cow<'static, str>,
+    pub(crate) tree_guide: OwoStyle,
 }
 
 impl Style {
-    pub(crate) fn style_field<'a>(
+    pub(crate) fn style_field<'a, V>(
         &'a self,
         color: ShouldColor,
         name: &'a str,
-        value: &'a str,
-    ) -> StyledField<'a> {
+        value: V,
+    ) -> StyledField<'a, V> {
         StyledField {
             color,
             name,
+            name_width: 0,
             name_style: self.field_name,
             value,
             value_style: self.field_value,
@@ -163,6 +214,26 @@ impl Style {
         self
     }
 
+    /// First-line indent text for the events written when spans close, used instead of the
+    /// [`Style::with_initial_indent_text`].
+    pub fn with_span_close_text(mut self, span_close_text: Cow<'static, str>) -> Self {
+        self.span_close_text = span_close_text;
+        self
+    }
+
+    /// Which spans to list after the event, and how.
+    pub fn with_span_display(mut self, span_display: SpanDisplay) -> Self {
+        self.span_display = span_display;
+        self
+    }
+
+    /// Guide text written once for each span an event is in, if
+    /// [`HumanLayer::with_span_tree`] is enabled.
+    pub fn with_tree_guide_text(mut self, tree_guide_text: Cow<'static, str>) -> Self {
+        self.tree_guide_text = tree_guide_text;
+        self
+    }
+
     /// Style for first-line indent text.
     pub fn with_initial_indent(mut self, initial_indent: OwoStyle) -> Self {
         self.initial_indent = initial_indent;
@@ -200,6 +271,31 @@ impl Style {
         self.span_in = span_in;
         self
     }
+
+    /// Style for timestamps, if enabled with [`HumanLayer::with_timestamps`].
+    pub fn with_timestamp(mut self, timestamp: OwoStyle) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Style for the guides written if [`HumanLayer::with_span_tree`] is enabled.
+    pub fn with_tree_guide(mut self, tree_guide: OwoStyle) -> Self {
+        self.tree_guide = tree_guide;
+        self
+    }
+
+    /// Style for the `caused by:` heading and numbering in an error field's list of
+    /// [`std::error::Error::source`]s.
+    pub fn with_error_caused_by(mut self, error_caused_by: OwoStyle) -> Self {
+        self.error_caused_by = error_caused_by;
+        self
+    }
+
+    /// Style for the messages in an error field's list of [`std::error::Error::source`]s.
+    pub fn with_error_source(mut self, error_source: OwoStyle) -> Self {
+        self.error_source = error_source;
+        self
+    }
 }
 
 pub(crate) trait IntoConditionalColor: Display {
@@ -228,22 +324,38 @@ where
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.color {
             ShouldColor::Always => self.style.style(&self.inner).fmt(f),
-            ShouldColor::Never => self.inner.fmt(f),
+            // `Auto` is resolved for the output writer before anything is formatted.
+            ShouldColor::Never | ShouldColor::Auto => self.inner.fmt(f),
         }
     }
 }
 
-pub(crate) struct StyledField<'a> {
+pub(crate) struct StyledField<'a, V> {
     color: ShouldColor,
     name: &'a str,
+    /// Pad the name with spaces to this width, to line up the `=` signs of several fields.
+    name_width: usize,
     name_style: OwoStyle,
-    value: &'a str,
+    value: V,
     value_style: OwoStyle,
 }
 
-impl Display for StyledField<'_> {
+impl<V> StyledField<'_, V> {
+    /// Pad the field's name with spaces to the given width.
+    pub(crate) fn with_name_width(mut self, name_width: usize) -> Self {
+        self.name_width = name_width;
+        self
+    }
+}
+
+impl<V> Display for StyledField<'_, V>
+where
+    V: Display,
+{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.name.colored(self.color, self.name_style))?;
+        let padding = self.name_width.saturating_sub(display_width(self.name));
+        write!(f, "{:padding$}", "")?;
         write!(f, "{}", '='.colored(self.color, self.value_style))?;
         write!(f, "{}", self.value.colored(self.color, self.value_style))?;
         Ok(())
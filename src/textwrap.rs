@@ -130,6 +130,16 @@ impl TextWrapOptionsOwned {
     }
 }
 
+impl TextWrapOptionsOwned {
+    /// The width in columns at which text will be wrapped.
+    pub(crate) fn width(&self) -> usize {
+        match self.width {
+            TextWrapWidth::TerminalWidth => textwrap::termwidth(),
+            TextWrapWidth::Fixed(width) => width,
+        }
+    }
+}
+
 impl Default for TextWrapOptionsOwned {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,126 @@
+//! Timestamps printed before each event.
+
+use std::time::Duration;
+use std::time::Instant;
+
+#[cfg(doc)]
+use crate::HumanLayer;
+
+/// How to format the timestamps printed before each event.
+///
+/// See [`HumanLayer::with_timestamps`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// Time since the [`HumanLayer`] was constructed, like `+12.345s`.
+    Uptime,
+    /// Time since the previous event written to the same writer, like `+0.012s`.
+    ///
+    /// The time is measured when the event is written, so the timestamps are in the same order
+    /// as the output, even when events are logged from several threads.
+    Delta,
+    /// Wall-clock time in the local time zone, like `14:03:27.123`.
+    Local,
+    /// An [RFC 3339](https://www.rfc-editor.org/rfc/rfc3339) timestamp in UTC, like
+    /// `2024-06-01T14:03:27.123Z`.
+    Rfc3339,
+}
+
+/// Produces timestamps in a given [`TimestampFormat`].
+#[derive(Debug)]
+pub(crate) struct Timestamps {
+    format: TimestampFormat,
+    /// When the layer was constructed.
+    start: Instant,
+}
+
+impl Timestamps {
+    pub(crate) fn new(format: TimestampFormat, start: Instant) -> Self {
+        Self { format, start }
+    }
+
+    pub(crate) fn format(&self) -> TimestampFormat {
+        self.format
+    }
+
+    /// Format the current time.
+    ///
+    /// Returns `None` for [`TimestampFormat::Delta`], which is measured with
+    /// [`Timestamps::delta`] when the event is written.
+    pub(crate) fn now(&self) -> Option<String> {
+        match self.format {
+            TimestampFormat::Uptime => Some(format_seconds(self.start.elapsed())),
+            TimestampFormat::Delta => None,
+            TimestampFormat::Local => Some(jiff::Zoned::now().strftime("%H:%M:%S%.3f").to_string()),
+            TimestampFormat::Rfc3339 => Some(format!("{:.3}", jiff::Timestamp::now())),
+        }
+    }
+
+    /// Format the time since the `last` event written to a writer (or since the layer was
+    /// constructed, if there isn't one), and record the current time as the last event.
+    pub(crate) fn delta(&self, last: &mut Option<Instant>) -> String {
+        let now = Instant::now();
+        let last = last.replace(now).unwrap_or(self.start);
+        format_seconds(now.saturating_duration_since(last))
+    }
+}
+
+fn format_seconds(duration: Duration) -> String {
+    format!("+{:.3}s", duration.as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_seconds() {
+        assert_eq!(format_seconds(Duration::ZERO), "+0.000s");
+        assert_eq!(
+            format_seconds(Duration::from_micros(12_345_678)),
+            "+12.346s"
+        );
+    }
+
+    #[test]
+    fn test_rfc3339() {
+        let timestamp = Timestamps::new(TimestampFormat::Rfc3339, Instant::now())
+            .now()
+            .unwrap();
+        assert_eq!(timestamp.len(), "2024-06-01T14:03:27.123Z".len());
+        assert!(timestamp.ends_with('Z'));
+    }
+
+    #[test]
+    fn test_local() {
+        let timestamp = Timestamps::new(TimestampFormat::Local, Instant::now())
+            .now()
+            .unwrap();
+        assert_eq!(timestamp.len(), "14:03:27.123".len());
+    }
+
+    /// Parse a timestamp like `+1.234s` into seconds.
+    fn parse_seconds(timestamp: &str) -> f64 {
+        timestamp
+            .strip_prefix('+')
+            .and_then(|timestamp| timestamp.strip_suffix('s'))
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_delta() {
+        // The monotonic clock may not go back 5 seconds, on a freshly booted machine.
+        let now = Instant::now();
+        let start = now.checked_sub(Duration::from_secs(5)).unwrap_or(now);
+        let timestamps = Timestamps::new(TimestampFormat::Delta, start);
+        assert_eq!(timestamps.now(), None);
+
+        // The first event is timed from when the layer was constructed.
+        let mut last = None;
+        let first = parse_seconds(&timestamps.delta(&mut last));
+        assert!(first >= now.duration_since(start).as_secs_f64());
+        assert!(last.is_some_and(|last| last >= now));
+        assert!(parse_seconds(&timestamps.delta(&mut last)) < 1.0);
+    }
+}
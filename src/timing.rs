@@ -0,0 +1,238 @@
+//! Tracking how long spans are open for.
+
+use std::fmt;
+use std::fmt::Display;
+use std::time::Duration;
+use std::time::Instant;
+
+#[cfg(doc)]
+use crate::HumanLayer;
+#[cfg(doc)]
+use crate::ProvideStyle;
+
+/// How long a span was open for, reported when it closes.
+///
+/// This is passed to [`ProvideStyle::for_span_close`], so that slow spans can be styled
+/// differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SpanTiming {
+    /// Time between the span being created and closed.
+    pub elapsed: Duration,
+    /// Time spent inside the span (between entering and exiting it).
+    pub busy: Duration,
+    /// Time spent outside the span while it was open.
+    pub idle: Duration,
+}
+
+/// Thresholds for showing [`SpanTiming`] information when a span closes.
+///
+/// Spans which close quickly aren't very interesting, so by default timing information is only
+/// shown for spans open for at least 100ms. Busy time is only shown if the span was idle for a
+/// significant amount of time, because otherwise it's the same as the elapsed time.
+#[derive(Debug, Clone, Copy)]
+pub struct SpanTimingThresholds {
+    elapsed: Duration,
+    idle: Duration,
+}
+
+impl SpanTimingThresholds {
+    /// Construct the default thresholds.
+    pub fn new() -> Self {
+        Self {
+            elapsed: Duration::from_millis(100),
+            idle: Duration::from_millis(100),
+        }
+    }
+
+    /// Show the elapsed time for spans which were open for at least this long.
+    pub fn with_elapsed(self, elapsed: Duration) -> Self {
+        Self { elapsed, ..self }
+    }
+
+    /// Show the busy time for spans which were idle for at least this long.
+    pub fn with_idle(self, idle: Duration) -> Self {
+        Self { idle, ..self }
+    }
+
+    /// Always show the elapsed and busy time.
+    pub fn always() -> Self {
+        Self {
+            elapsed: Duration::ZERO,
+            idle: Duration::ZERO,
+        }
+    }
+
+    /// Format a message for a closed span.
+    pub(crate) fn message(&self, name: &str, timing: SpanTiming) -> String {
+        let mut message = format!("{name} finished");
+        if timing.elapsed >= self.elapsed {
+            message.push_str(&format!(" in {}", HumanDuration(timing.elapsed)));
+            if timing.idle >= self.idle && timing.idle > Duration::ZERO {
+                message.push_str(&format!(" (busy {})", HumanDuration(timing.busy)));
+            }
+        }
+        message
+    }
+}
+
+impl Default for SpanTimingThresholds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Timestamps tracked for each span in its extensions, like `tracing_subscriber::fmt` does.
+#[derive(Debug)]
+pub(crate) struct SpanTimings {
+    created: Instant,
+    /// When the span was last entered or exited.
+    last: Instant,
+    busy: Duration,
+    idle: Duration,
+}
+
+impl SpanTimings {
+    pub(crate) fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            created: now,
+            last: now,
+            busy: Duration::ZERO,
+            idle: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn enter(&mut self) {
+        let now = Instant::now();
+        self.idle += now.saturating_duration_since(self.last);
+        self.last = now;
+    }
+
+    pub(crate) fn exit(&mut self) {
+        let now = Instant::now();
+        self.busy += now.saturating_duration_since(self.last);
+        self.last = now;
+    }
+
+    /// Get the [`SpanTiming`] for a closing span.
+    pub(crate) fn close(&self) -> SpanTiming {
+        let now = Instant::now();
+        SpanTiming {
+            elapsed: now.saturating_duration_since(self.created),
+            busy: self.busy,
+            idle: self.idle + now.saturating_duration_since(self.last),
+        }
+    }
+}
+
+/// Formats a [`Duration`] with about three significant figures, like `1.42s` or `250ms`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HumanDuration(pub(crate) Duration);
+
+impl Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0.as_secs();
+        if secs >= 60 * 60 {
+            write!(f, "{}h {:02}m", secs / (60 * 60), secs / 60 % 60)
+        } else if secs >= 60 {
+            write!(f, "{}m {:02}s", secs / 60, secs % 60)
+        } else {
+            const UNITS: [(f64, &str); 4] = [(1.0, "ns"), (1e3, "µs"), (1e6, "ms"), (1e9, "s")];
+            let nanos = self.0.as_nanos() as f64;
+            let mut unit = UNITS
+                .iter()
+                .rposition(|(scale, _)| nanos >= *scale)
+                .unwrap_or_default();
+            // Round before settling on a unit, so that 999.9µs is `1ms` rather than `1000µs`.
+            let (value, precision) = loop {
+                let value = nanos / UNITS[unit].0;
+                let precision = if value >= 100.0 {
+                    0
+                } else if value >= 10.0 {
+                    1
+                } else {
+                    2
+                };
+                let scale = 10_f64.powi(precision as i32);
+                let rounded = (value * scale).round() / scale;
+                if rounded >= 1000.0 && unit < UNITS.len() - 1 {
+                    unit += 1;
+                } else {
+                    break (rounded, precision);
+                }
+            };
+            let unit = UNITS[unit].1;
+            if unit == "s" && value >= 60.0 {
+                return HumanDuration(Duration::from_secs(60)).fmt(f);
+            }
+            let value = format!("{value:.precision$}");
+            let value = if value.contains('.') {
+                value.trim_end_matches('0').trim_end_matches('.')
+            } else {
+                &value
+            };
+            write!(f, "{value}{unit}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn human(duration: Duration) -> String {
+        HumanDuration(duration).to_string()
+    }
+
+    #[test]
+    fn test_human_duration() {
+        assert_eq!(human(Duration::from_nanos(15)), "15ns");
+        assert_eq!(human(Duration::from_nanos(1_500)), "1.5µs");
+        assert_eq!(human(Duration::from_micros(250)), "250µs");
+        assert_eq!(human(Duration::from_micros(12_345)), "12.3ms");
+        assert_eq!(human(Duration::from_millis(1_420)), "1.42s");
+        assert_eq!(human(Duration::from_millis(1_100)), "1.1s");
+        assert_eq!(human(Duration::from_secs(2)), "2s");
+        assert_eq!(human(Duration::from_secs(125)), "2m 05s");
+        assert_eq!(human(Duration::from_secs(3_720)), "1h 02m");
+    }
+
+    #[test]
+    fn test_human_duration_rounding() {
+        // Values which round up to the next unit are written in that unit.
+        assert_eq!(human(Duration::from_nanos(999_900)), "1ms");
+        assert_eq!(human(Duration::from_micros(999_960)), "1s");
+        assert_eq!(human(Duration::from_millis(59_999)), "1m 00s");
+        assert_eq!(human(Duration::from_nanos(999_600)), "1ms");
+        assert_eq!(human(Duration::from_nanos(999_400)), "999µs");
+        assert_eq!(human(Duration::from_nanos(999)), "999ns");
+        assert_eq!(human(Duration::from_nanos(9_999)), "10µs");
+    }
+
+    #[test]
+    fn test_message() {
+        let timing = SpanTiming {
+            elapsed: Duration::from_millis(1_420),
+            busy: Duration::from_millis(1_100),
+            idle: Duration::from_millis(320),
+        };
+        let thresholds = SpanTimingThresholds::new();
+        assert_eq!(
+            thresholds.message("copy", timing),
+            "copy finished in 1.42s (busy 1.1s)"
+        );
+        assert_eq!(
+            thresholds
+                .with_idle(Duration::from_secs(1))
+                .message("copy", timing),
+            "copy finished in 1.42s"
+        );
+        assert_eq!(
+            thresholds
+                .with_elapsed(Duration::from_secs(2))
+                .message("copy", timing),
+            "copy finished"
+        );
+    }
+}
@@ -0,0 +1,74 @@
+//! What to do when writing output fails.
+
+use std::fmt::Debug;
+use std::io;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+#[cfg(doc)]
+use crate::HumanLayer;
+
+/// What a [`HumanLayer`] does when writing its output fails, like when the output is a closed
+/// pipe or a full disk.
+///
+/// See [`HumanLayer::with_write_error_policy`].
+#[derive(Clone, Default)]
+pub enum WriteErrorPolicy {
+    /// Ignore errors, and keep writing events.
+    #[default]
+    Ignore,
+    /// Call a function with each error.
+    ///
+    /// The function is called after the output is unlocked, so it can log the error. If writing
+    /// the events it logs fails too, the function isn't called again for those errors.
+    Callback(Arc<dyn Fn(&io::Error) + Send + Sync>),
+    /// Count errors with a [`WriteErrorCount`].
+    Count(WriteErrorCount),
+    /// Stop formatting and writing events after a write fails with
+    /// [`io::ErrorKind::BrokenPipe`], like when the output is piped to `head`. Other errors
+    /// are ignored.
+    DisableOnBrokenPipe,
+}
+
+impl WriteErrorPolicy {
+    /// Call a function with each error. See [`WriteErrorPolicy::Callback`].
+    pub fn callback(callback: impl Fn(&io::Error) + Send + Sync + 'static) -> Self {
+        Self::Callback(Arc::new(callback))
+    }
+}
+
+impl Debug for WriteErrorPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ignore => write!(f, "Ignore"),
+            Self::Callback(_) => f.debug_tuple("Callback").finish_non_exhaustive(),
+            Self::Count(count) => f.debug_tuple("Count").field(count).finish(),
+            Self::DisableOnBrokenPipe => write!(f, "DisableOnBrokenPipe"),
+        }
+    }
+}
+
+/// A count of the errors writing a [`HumanLayer`]'s output, used with
+/// [`WriteErrorPolicy::Count`].
+///
+/// Clones share the same count, so one can be kept to check the count after the other is
+/// given to the layer.
+#[derive(Debug, Clone, Default)]
+pub struct WriteErrorCount(Arc<AtomicUsize>);
+
+impl WriteErrorCount {
+    /// Construct a new count, starting at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the number of errors so far.
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
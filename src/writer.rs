@@ -0,0 +1,96 @@
+//! The writers a [`HumanLayer`] can write its output to.
+
+use std::fmt::Debug;
+use std::io;
+use std::io::Write;
+
+use tracing::Metadata;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::MaybeTerminal;
+
+#[cfg(doc)]
+use crate::HumanLayer;
+
+/// Where a [`HumanLayer`] writes its output: any [`Write`] implementation, set with
+/// [`HumanLayer::with_output_writer`], or a [`MakeWriter`], set with
+/// [`HumanLayer::with_make_writer`].
+///
+/// This trait is sealed, so it can't be implemented outside of this crate.
+pub trait OutputWriter: sealed::Sealed {
+    /// Write all of `bytes`, which are the output for an event with the given `metadata`, or
+    /// the progress bars and status footer if there's no `metadata`.
+    #[doc(hidden)]
+    fn write_output(&mut self, metadata: Option<&Metadata<'_>>, bytes: &[u8]) -> io::Result<()>;
+
+    /// Flush the output.
+    #[doc(hidden)]
+    fn flush_output(&mut self) -> io::Result<()>;
+}
+
+impl<W> OutputWriter for W
+where
+    W: Write,
+{
+    fn write_output(&mut self, _metadata: Option<&Metadata<'_>>, bytes: &[u8]) -> io::Result<()> {
+        self.write_all(bytes)
+    }
+
+    fn flush_output(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+/// A [`MakeWriter`] used as a [`HumanLayer`]'s output. See [`HumanLayer::with_make_writer`].
+///
+/// A writer is made for each event with [`MakeWriter::make_writer_for`], so output can be
+/// sent to different places depending on the event's metadata.
+pub struct MakeWriterOutput<M> {
+    make_writer: M,
+}
+
+impl<M> MakeWriterOutput<M> {
+    pub(crate) fn new(make_writer: M) -> Self {
+        Self { make_writer }
+    }
+}
+
+/// A [`MakeWriter`] can't tell whether its output is a terminal.
+impl<M> MaybeTerminal for MakeWriterOutput<M> {}
+
+impl<M> Debug for MakeWriterOutput<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MakeWriterOutput").finish_non_exhaustive()
+    }
+}
+
+impl<M> OutputWriter for MakeWriterOutput<M>
+where
+    M: for<'a> MakeWriter<'a>,
+{
+    fn write_output(&mut self, metadata: Option<&Metadata<'_>>, bytes: &[u8]) -> io::Result<()> {
+        let mut writer = match metadata {
+            Some(metadata) => self.make_writer.make_writer_for(metadata),
+            None => self.make_writer.make_writer(),
+        };
+        writer.write_all(bytes)?;
+        writer.flush()
+    }
+
+    /// Each writer is flushed after it's written to, so there's nothing to do.
+    fn flush_output(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+mod sealed {
+    use std::io::Write;
+
+    use super::MakeWriterOutput;
+
+    pub trait Sealed {}
+
+    impl<W> Sealed for W where W: Write {}
+
+    impl<M> Sealed for MakeWriterOutput<M> {}
+}
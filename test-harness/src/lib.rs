@@ -44,6 +44,7 @@ impl std::error::Error for TestError {}
 pub struct Example {
     name: String,
     args: Vec<String>,
+    envs: Vec<(String, String)>,
 }
 
 impl Example {
@@ -51,6 +52,7 @@ impl Example {
         Example {
             name: name.as_ref().to_owned(),
             args: Default::default(),
+            envs: Default::default(),
         }
     }
 
@@ -106,11 +108,18 @@ impl Example {
         self
     }
 
+    pub fn env(&mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> &mut Self {
+        self.envs
+            .push((key.as_ref().to_owned(), value.as_ref().to_owned()));
+        self
+    }
+
     pub fn output(&self) -> Result<Utf8Output> {
         let executable = self.executable()?;
 
         Ok(Command::new(executable)
             .args(&self.args)
+            .envs(self.envs.iter().map(|(key, value)| (key, value)))
             .output_checked_utf8()?)
     }
 }
@@ -11,7 +11,7 @@ fn test_output_stream_stdout() {
     let stdout = expect![[r#"
         TRACE Trace event.
         DEBUG Debug event.
-        • Info event. field="field-value"
+        • Info event. field=field-value
         ⚠ Warn event.
         ⚠ Error event.
 
@@ -23,61 +23,61 @@ fn test_output_stream_stdout() {
           incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis
           nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat.
 
-        • Info event.
-          field="field-value"
-          other_field="my-other-field-value"
+        • Info event. field=field-value other_field=my-other-field-value
+        ⚠ Failed to start.
+          error=Failed to read `config.toml`
+            caused by:
+            1. No such file or directory
         • new
-          in my-span{path="my/cool/path.txt"}
+          in my-span{path=my/cool/path.txt}
         • new
-          in copy{path="my/cool/path.txt" other_path="my/second/path.txt"}
-          in my-span{path="my/cool/path.txt"}
+          in copy{path=my/cool/path.txt other_path=my/second/path.txt}
+          in my-span{path=my/cool/path.txt}
         • new
           in my-inner-span
-          in copy{path="my/cool/path.txt" other_path="my/second/path.txt"}
-          in my-span{path="my/cool/path.txt"}
+          in copy{path=my/cool/path.txt other_path=my/second/path.txt}
+          in my-span{path=my/cool/path.txt}
         TRACE Trace event.
           in my-inner-span
-          in copy{path="my/cool/path.txt" other_path="my/second/path.txt"}
-          in my-span{path="my/cool/path.txt"}
+          in copy{path=my/cool/path.txt other_path=my/second/path.txt}
+          in my-span{path=my/cool/path.txt}
         DEBUG Debug event.
           in my-inner-span
-          in copy{path="my/cool/path.txt" other_path="my/second/path.txt"}
-          in my-span{path="my/cool/path.txt"}
-        • Info event. field="field-value"
+          in copy{path=my/cool/path.txt other_path=my/second/path.txt}
+          in my-span{path=my/cool/path.txt}
+        • Info event. field=field-value
           in my-inner-span
-          in copy{path="my/cool/path.txt" other_path="my/second/path.txt"}
-          in my-span{path="my/cool/path.txt"}
+          in copy{path=my/cool/path.txt other_path=my/second/path.txt}
+          in my-span{path=my/cool/path.txt}
         ⚠ Warn event.
           in my-inner-span
-          in copy{path="my/cool/path.txt" other_path="my/second/path.txt"}
-          in my-span{path="my/cool/path.txt"}
+          in copy{path=my/cool/path.txt other_path=my/second/path.txt}
+          in my-span{path=my/cool/path.txt}
         ⚠ Error event.
           in my-inner-span
-          in copy{path="my/cool/path.txt" other_path="my/second/path.txt"}
-          in my-span{path="my/cool/path.txt"}
+          in copy{path=my/cool/path.txt other_path=my/second/path.txt}
+          in my-span{path=my/cool/path.txt}
 
         • Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor
           incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis
           nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat.
           in my-inner-span
-          in copy{path="my/cool/path.txt" other_path="my/second/path.txt"}
-          in my-span{path="my/cool/path.txt"}
+          in copy{path=my/cool/path.txt other_path=my/second/path.txt}
+          in my-span{path=my/cool/path.txt}
 
-        • Info event.
-          field="field-value"
-          other_field="my-other-field-value"
+        • Info event. field=field-value other_field=my-other-field-value
           in my-inner-span
-          in copy{path="my/cool/path.txt" other_path="my/second/path.txt"}
-          in my-span{path="my/cool/path.txt"}
-        • close
+          in copy{path=my/cool/path.txt other_path=my/second/path.txt}
+          in my-span{path=my/cool/path.txt}
+        ✓ my-inner-span finished
           in my-inner-span
-          in copy{path="my/cool/path.txt" other_path="my/second/path.txt"}
-          in my-span{path="my/cool/path.txt"}
-        • close
-          in copy{path="my/cool/path.txt" other_path="my/second/path.txt"}
-          in my-span{path="my/cool/path.txt"}
-        • close
-          in my-span{path="my/cool/path.txt"}
+          in copy{path=my/cool/path.txt other_path=my/second/path.txt}
+          in my-span{path=my/cool/path.txt}
+        ✓ copy finished
+          in copy{path=my/cool/path.txt other_path=my/second/path.txt}
+          in my-span{path=my/cool/path.txt}
+        ✓ my-span finished
+          in my-span{path=my/cool/path.txt}
     "#]];
     stdout.assert_eq(&output.stdout);
 }
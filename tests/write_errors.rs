@@ -0,0 +1,54 @@
+use std::io;
+use std::io::Write;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use tracing_human_layer::HumanLayer;
+use tracing_human_layer::MaybeTerminal;
+use tracing_human_layer::WriteErrorPolicy;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// A writer which always fails.
+struct FailingWriter;
+
+impl Write for FailingWriter {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::from(io::ErrorKind::StorageFull))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl MaybeTerminal for FailingWriter {}
+
+/// Only the global dispatcher handles events logged while handling another event, so this test
+/// sets it, and gets a test binary to itself.
+#[test]
+fn test_write_error_callback_logs() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let policy = {
+        let calls = calls.clone();
+        WriteErrorPolicy::callback(move |error| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            // Writing this event fails too.
+            tracing::warn!(%error, "Failed to write output.");
+        })
+    };
+    tracing_subscriber::registry()
+        .with(
+            HumanLayer::new()
+                .with_write_error_policy(policy)
+                .with_output_writer(FailingWriter),
+        )
+        .init();
+
+    tracing::info!("Copying.");
+    tracing::info!("Copied.");
+
+    // The callback isn't called for the events it logs.
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+}